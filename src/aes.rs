@@ -1,212 +1,807 @@
 use bytes::{inplace_xor, repeat_xor};
-use crypto::{symmetriccipher, buffer, aes, blockmodes};
-use crypto::buffer::{BufferResult, WriteBuffer, ReadBuffer};
-
-const BLOCK_SIZE: usize = 16;
-
-pub fn encrypt_ecb(data: &[u8],
-                   key: &[u8])
-                   -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    if key.len() * 8 != 128 {
-        return Err(symmetriccipher::SymmetricCipherError::InvalidLength);
-    }
-
-    let keysize = aes::KeySize::KeySize128;
-
-    let mut encryptor = aes::ecb_encryptor(keysize, key, blockmodes::NoPadding);
-
-    // Each encryption operation encrypts some data from
-    // an input buffer into an output buffer. Those buffers
-    // must be instances of RefReaderBuffer and RefWriteBuffer
-    // (respectively) which keep track of how much data has been
-    // read from or written to them.
-    let mut final_result = Vec::<u8>::new();
-    let mut read_buffer = buffer::RefReadBuffer::new(data);
-    let mut buffer = [0; 4096];
-    let mut write_buffer = buffer::RefWriteBuffer::new(&mut buffer);
-
-    // Each encryption operation will "make progress". "Making progress"
-    // is a bit loosely defined, but basically, at the end of each operation
-    // either BufferUnderflow or BufferOverflow will be returned (unless
-    // there was an error). If the return value is BufferUnderflow, it means
-    // that the operation ended while wanting more input data. If the return
-    // value is BufferOverflow, it means that the operation ended because it
-    // needed more space to output data. As long as the next call to the encryption
-    // operation provides the space that was requested (either more input data
-    // or more output space), the operation is guaranteed to get closer to
-    // completing the full operation - ie: "make progress".
-    //
-    // Here, we pass the data to encrypt to the enryptor along with a fixed-size
-    // output buffer. The 'true' flag indicates that the end of the data that
-    // is to be encrypted is included in the input buffer (which is true, since
-    // the input data includes all the data to encrypt). After each call, we copy
-    // any output data to our result Vec. If we get a BufferOverflow, we keep
-    // going in the loop since it means that there is more work to do. We can
-    // complete as soon as we get a BufferUnderflow since the encryptor is telling
-    // us that it stopped processing data due to not having any more data in the
-    // input buffer.
-    loop {
-        let result = try!(encryptor.encrypt(&mut read_buffer, &mut write_buffer, true));
-
-        // "write_buffer.take_read_buffer().take_remaining()" means:
-        // from the writable buffer, create a new readable buffer which
-        // contains all data that has been written, and then access all
-        // of that data as a slice.
-        final_result.extend(write_buffer
-                                .take_read_buffer()
-                                .take_remaining()
-                                .iter()
-                                .map(|&i| i));
-
-        match result {
-            BufferResult::BufferUnderflow => break,
-            BufferResult::BufferOverflow => {}
-        }
-    }
+use aescore::{Aes, BLOCK_SIZE};
+use blockcipher::BlockCipher;
+use padding::{Padding, Pkcs7};
+pub use blockcipher::CipherError;
+pub use bytes::PaddingError;
 
-    Ok(final_result)
-}
-
-// Decrypts a buffer with the given key and iv using
-// AES-256/CBC/Pkcs encryption.
-//
-// This function is very similar to encrypt(), so, please reference
-// comments in that function. In non-example code, if desired, it is possible to
-// share much of the implementation using closures to hide the operation
-// being performed. However, such code would make this example less clear.
-pub fn decrypt_ecb(encrypted_data: &[u8],
-                   key: &[u8])
-                   -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    if key.len() * 8 != 128 {
-        return Err(symmetriccipher::SymmetricCipherError::InvalidLength);
-    }
-    let keysize = aes::KeySize::KeySize128;
-
-    let mut decryptor = aes::ecb_decryptor(keysize, key, blockmodes::NoPadding);
-
-    let mut final_result = Vec::<u8>::new();
-    let mut read_buffer = buffer::RefReadBuffer::new(encrypted_data);
-    let mut buffer = [0; 4096];
-    let mut write_buffer = buffer::RefWriteBuffer::new(&mut buffer);
-
-    loop {
-        let result = try!(decryptor.decrypt(&mut read_buffer, &mut write_buffer, true));
-        final_result.extend(write_buffer
-                                .take_read_buffer()
-                                .take_remaining()
-                                .iter()
-                                .map(|&i| i));
-        match result {
-            BufferResult::BufferUnderflow => break,
-            BufferResult::BufferOverflow => {}
-        }
-    }
+// The key size (128, 192 or 256 bits) is checked by Aes::new, so all of the
+// key-based functions below work with any of them.  The `_with` versions take
+// any BlockCipher and are where the modes are actually implemented.
+
+pub fn encrypt_ecb(data: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_ecb_with(&try!(Aes::new(key)), data)
+}
 
-    Ok(final_result)
+pub fn decrypt_ecb(encrypted_data: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_ecb_with(&try!(Aes::new(key)), encrypted_data)
 }
 
 pub fn decrypt_cbc(ciphertext: &[u8],
                    key: &[u8],
                    iv: &[u8])
-                   -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    assert_eq!(key.len(), iv.len());
-    let mut cleartext = Vec::with_capacity(ciphertext.len());
-    let mut chunks = ciphertext.chunks(key.len());
-
-    match chunks.next() {
-        Some(c) => {
-            assert_eq!(c.len(), key.len());
-            // do the first block
-            let mut block = try!(decrypt_ecb(c, key));
-            block = inplace_xor(block, iv);
-            cleartext.extend_from_slice(&block);
-            let mut last_block = c;
-
-            // do the rest of the blocks
-            for c in chunks {
-                assert_eq!(c.len(), key.len());
-                let mut block = try!(decrypt_ecb(c, key));
-                block = inplace_xor(block, &last_block);
-                cleartext.extend_from_slice(&block);
-                last_block = c;
-            }
-
-            Ok(cleartext)
-        }
-        None => Ok(cleartext),
-    }
+                   -> Result<Vec<u8>, CipherError> {
+    decrypt_cbc_with(&try!(Aes::new(key)), ciphertext, iv)
 }
 
 pub fn encrypt_cbc(cleartext: &[u8],
                    key: &[u8],
                    iv: &[u8])
-                   -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    assert_eq!(key.len(), iv.len());
-    let mut ciphertext = Vec::with_capacity(cleartext.len());
-    let mut chunks = cleartext.chunks(key.len());
-
-    match chunks.next() {
-        Some(c) => {
-            assert_eq!(c.len(), key.len());
-            // do the first block
-            let mut block = repeat_xor(c, iv);
-            // TODO - there's an extra alloc here - we could have an inplace
-            // encrypt ecb
-            block = try!(encrypt_ecb(&block, key));
-            ciphertext.extend_from_slice(&block);
-            let mut last_block = block;
-
-            // do the rest of the blocks
-            for c in chunks {
-                assert_eq!(c.len(), key.len());
-                let mut block = repeat_xor(c, &last_block);
-                block = try!(encrypt_ecb(&block, key));
-                ciphertext.extend_from_slice(&block);
-                last_block = block;
+                   -> Result<Vec<u8>, CipherError> {
+    encrypt_cbc_with(&try!(Aes::new(key)), cleartext, iv)
+}
+
+pub fn decrypt_ctr(ciphertext: &[u8],
+                   key: &[u8],
+                   nonce: &u64)
+                   -> Result<Vec<u8>, CipherError> {
+    decrypt_ctr_with(&try!(Aes::new(key)), ciphertext, nonce)
+}
+
+// it's just the reverse of decrypt_ctr
+pub fn encrypt_ctr(cleartext: &[u8],
+                   key: &[u8],
+                   nonce: &u64)
+                   -> Result<Vec<u8>, CipherError> {
+    decrypt_ctr(cleartext, key, nonce)
+}
+
+// In-place versions of the above.  Anything doing this more than once should
+// make an Aes up front and use the _in_place_with versions, rather than
+// expanding the key every time.
+#[allow(dead_code)]
+pub fn encrypt_block_in_place(block: &mut [u8], key: &[u8]) -> Result<(), CipherError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(CipherError::InvalidLength);
+    }
+    try!(Aes::new(key)).encrypt_block(block);
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn decrypt_block_in_place(block: &mut [u8], key: &[u8]) -> Result<(), CipherError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(CipherError::InvalidLength);
+    }
+    try!(Aes::new(key)).decrypt_block(block);
+    Ok(())
+}
+
+pub fn encrypt_cbc_in_place(data: &mut [u8], key: &[u8], iv: &[u8]) -> Result<(), CipherError> {
+    encrypt_cbc_in_place_with(&try!(Aes::new(key)), data, iv)
+}
+
+pub fn decrypt_cbc_in_place(data: &mut [u8], key: &[u8], iv: &[u8]) -> Result<(), CipherError> {
+    decrypt_cbc_in_place_with(&try!(Aes::new(key)), data, iv)
+}
+
+pub fn apply_ctr_in_place(data: &mut [u8], key: &[u8], nonce: &u64) -> Result<(), CipherError> {
+    apply_ctr_in_place_with(&try!(Aes::new(key)), data, nonce);
+    Ok(())
+}
+
+// The _padded versions add pkcs7 padding before encrypting and check and
+// remove it after decrypting, so callers don't have to.
+pub fn encrypt_ecb_padded(cleartext: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_ecb_with_padding(cleartext, key, &Pkcs7)
+}
+
+pub fn decrypt_ecb_padded(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_ecb_with_padding(ciphertext, key, &Pkcs7)
+}
+
+#[allow(dead_code)]
+pub fn encrypt_cbc_padded(cleartext: &[u8],
+                          key: &[u8],
+                          iv: &[u8])
+                          -> Result<Vec<u8>, CipherError> {
+    encrypt_cbc_with_padding(cleartext, key, iv, &Pkcs7)
+}
+
+pub fn decrypt_cbc_padded(ciphertext: &[u8],
+                          key: &[u8],
+                          iv: &[u8])
+                          -> Result<Vec<u8>, CipherError> {
+    decrypt_cbc_with_padding(ciphertext, key, iv, &Pkcs7)
+}
+
+// ...and the same with any padding scheme
+pub fn encrypt_ecb_with_padding<P: Padding>(cleartext: &[u8],
+                                            key: &[u8],
+                                            padding: &P)
+                                            -> Result<Vec<u8>, CipherError> {
+    encrypt_ecb(&padding.pad(cleartext.to_vec(), BLOCK_SIZE), key)
+}
+
+pub fn decrypt_ecb_with_padding<P: Padding>(ciphertext: &[u8],
+                                            key: &[u8],
+                                            padding: &P)
+                                            -> Result<Vec<u8>, CipherError> {
+    if ciphertext.len() % BLOCK_SIZE != 0 {
+        return Err(CipherError::Padding(PaddingError::NotBlockAligned));
+    }
+    let cleartext = try!(decrypt_ecb(ciphertext, key));
+    Ok(try!(padding.unpad(cleartext, BLOCK_SIZE)))
+}
+
+#[allow(dead_code)]
+pub fn encrypt_cbc_with_padding<P: Padding>(cleartext: &[u8],
+                                            key: &[u8],
+                                            iv: &[u8],
+                                            padding: &P)
+                                            -> Result<Vec<u8>, CipherError> {
+    encrypt_cbc(&padding.pad(cleartext.to_vec(), BLOCK_SIZE), key, iv)
+}
+
+pub fn decrypt_cbc_with_padding<P: Padding>(ciphertext: &[u8],
+                                            key: &[u8],
+                                            iv: &[u8],
+                                            padding: &P)
+                                            -> Result<Vec<u8>, CipherError> {
+    // decrypt_cbc insists on whole blocks
+    if ciphertext.len() % BLOCK_SIZE != 0 {
+        return Err(CipherError::Padding(PaddingError::NotBlockAligned));
+    }
+    let cleartext = try!(decrypt_cbc(ciphertext, key, iv));
+    Ok(try!(padding.unpad(cleartext, BLOCK_SIZE)))
+}
+
+#[allow(dead_code)]
+pub fn encrypt_cbc_cts(cleartext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_cbc_cts_with(&try!(Aes::new(key)), cleartext, iv)
+}
+
+#[allow(dead_code)]
+pub fn decrypt_cbc_cts(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_cbc_cts_with(&try!(Aes::new(key)), ciphertext, iv)
+}
+
+// OFB, CFB and PCBC, mostly for comparing how each one spreads errors
+
+pub fn encrypt_ofb(cleartext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_ofb_with(&try!(Aes::new(key)), cleartext, iv)
+}
+
+pub fn decrypt_ofb(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_ofb_with(&try!(Aes::new(key)), ciphertext, iv)
+}
+
+pub fn encrypt_cfb128(cleartext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_cfb_with(&try!(Aes::new(key)), cleartext, iv)
+}
+
+pub fn decrypt_cfb128(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_cfb_with(&try!(Aes::new(key)), ciphertext, iv)
+}
+
+pub fn encrypt_cfb8(cleartext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_cfb8_with(&try!(Aes::new(key)), cleartext, iv)
+}
+
+pub fn decrypt_cfb8(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_cfb8_with(&try!(Aes::new(key)), ciphertext, iv)
+}
+
+pub fn encrypt_pcbc(cleartext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_pcbc_with(&try!(Aes::new(key)), cleartext, iv)
+}
+
+pub fn decrypt_pcbc(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_pcbc_with(&try!(Aes::new(key)), ciphertext, iv)
+}
+
+pub fn encrypt_ecb_with<C: BlockCipher>(cipher: &C, data: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let block_size = cipher.block_size();
+    if data.len() % block_size != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+
+    let mut ciphertext = data.to_vec();
+    for block in ciphertext.chunks_mut(block_size) {
+        cipher.encrypt_block(block);
+    }
+
+    Ok(ciphertext)
+}
+
+pub fn decrypt_ecb_with<C: BlockCipher>(cipher: &C,
+                                        encrypted_data: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    let block_size = cipher.block_size();
+    if encrypted_data.len() % block_size != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+
+    let mut cleartext = encrypted_data.to_vec();
+    for block in cleartext.chunks_mut(block_size) {
+        cipher.decrypt_block(block);
+    }
+
+    Ok(cleartext)
+}
+
+pub fn decrypt_cbc_with<C: BlockCipher>(cipher: &C,
+                                        ciphertext: &[u8],
+                                        iv: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    let mut cleartext = ciphertext.to_vec();
+    try!(decrypt_cbc_in_place_with(cipher, &mut cleartext, iv));
+    Ok(cleartext)
+}
+
+pub fn encrypt_cbc_with<C: BlockCipher>(cipher: &C,
+                                        cleartext: &[u8],
+                                        iv: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    let mut ciphertext = cleartext.to_vec();
+    try!(encrypt_cbc_in_place_with(cipher, &mut ciphertext, iv));
+    Ok(ciphertext)
+}
+
+// The in-place versions don't allocate at all.  Each block is xored with the
+// ciphertext block before it, which is already sitting in data.
+pub fn encrypt_cbc_in_place_with<C: BlockCipher>(cipher: &C,
+                                                 data: &mut [u8],
+                                                 iv: &[u8])
+                                                 -> Result<(), CipherError> {
+    let block_size = cipher.block_size();
+    if iv.len() != block_size || data.len() % block_size != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+
+    for start in (0..data.len()).step_by(block_size) {
+        {
+            let (previous, block) = data.split_at_mut(start);
+            let feedback = if start == 0 {
+                iv
+            } else {
+                &previous[start - block_size..]
+            };
+            for (b, f) in block[..block_size].iter_mut().zip(feedback) {
+                *b ^= *f;
             }
+        }
+        cipher.encrypt_block(&mut data[start..start + block_size]);
+    }
+    Ok(())
+}
+
+// Decrypting works backwards from the last block, so the ciphertext block
+// each one needs is still there.
+pub fn decrypt_cbc_in_place_with<C: BlockCipher>(cipher: &C,
+                                                 data: &mut [u8],
+                                                 iv: &[u8])
+                                                 -> Result<(), CipherError> {
+    let block_size = cipher.block_size();
+    if iv.len() != block_size || data.len() % block_size != 0 {
+        return Err(CipherError::InvalidLength);
+    }
 
-            Ok(ciphertext)
+    for start in (0..data.len()).step_by(block_size).rev() {
+        let (previous, block) = data.split_at_mut(start);
+        let block = &mut block[..block_size];
+        cipher.decrypt_block(block);
+        let feedback = if start == 0 {
+            iv
+        } else {
+            &previous[start - block_size..]
+        };
+        for (b, f) in block.iter_mut().zip(feedback) {
+            *b ^= *f;
         }
-        None => Ok(ciphertext),
     }
+    Ok(())
 }
 
-fn serialize_little_endian(val: &u64, dest: &mut [u8]) {
-    dest[0] = (val & 0xff) as u8;
-    dest[1] = ((val >> 8) & 0xffu64) as u8;
-    dest[2] = ((val >> 16) & 0xffu64) as u8;
-    dest[3] = ((val >> 24) & 0xffu64) as u8;
-    dest[4] = ((val >> 32) & 0xffu64) as u8;
-    dest[5] = ((val >> 40) & 0xffu64) as u8;
-    dest[6] = ((val >> 48) & 0xffu64) as u8;
-    dest[7] = ((val >> 56) & 0xffu64) as u8;
+// CBC with ciphertext stealing, the CS3 variant (as used by Kerberos).  The
+// last block is zero padded and encrypted as usual, then the last two
+// ciphertext blocks are swapped and the one that ends up last is cut down to
+// the length of the last cleartext block.  The ciphertext is the same length
+// as the cleartext, which has to be at least one block.
+#[allow(dead_code)]
+pub fn encrypt_cbc_cts_with<C: BlockCipher>(cipher: &C,
+                                            cleartext: &[u8],
+                                            iv: &[u8])
+                                            -> Result<Vec<u8>, CipherError> {
+    let block_size = cipher.block_size();
+    let len = cleartext.len();
+    if len < block_size {
+        return Err(CipherError::InvalidLength);
+    }
+    if len == block_size {
+        return encrypt_cbc_with(cipher, cleartext, iv);
+    }
+
+    let num_blocks = (len + block_size - 1) / block_size;
+    let last_len = len - (num_blocks - 1) * block_size;
+    let mut padded = cleartext.to_vec();
+    padded.resize(num_blocks * block_size, 0);
+    let mut ciphertext = try!(encrypt_cbc_with(cipher, &padded, iv));
+
+    let second_last_start = (num_blocks - 2) * block_size;
+    let stolen = ciphertext[second_last_start..second_last_start + last_len].to_vec();
+    let last = ciphertext[second_last_start + block_size..].to_vec();
+    ciphertext.truncate(second_last_start);
+    ciphertext.extend(last);
+    ciphertext.extend(stolen);
+    Ok(ciphertext)
 }
 
-pub fn decrypt_ctr(ciphertext: &[u8],
-                   key: &[u8],
-                   nonce: &u64)
-                   -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
+#[allow(dead_code)]
+pub fn decrypt_cbc_cts_with<C: BlockCipher>(cipher: &C,
+                                            ciphertext: &[u8],
+                                            iv: &[u8])
+                                            -> Result<Vec<u8>, CipherError> {
+    let block_size = cipher.block_size();
+    let len = ciphertext.len();
+    if len < block_size {
+        return Err(CipherError::InvalidLength);
+    }
+    if len == block_size {
+        return decrypt_cbc_with(cipher, ciphertext, iv);
+    }
 
+    let num_blocks = (len + block_size - 1) / block_size;
+    let last_len = len - (num_blocks - 1) * block_size;
+    let second_last_start = (num_blocks - 2) * block_size;
+    let last = &ciphertext[second_last_start..second_last_start + block_size];
+    let stolen = &ciphertext[second_last_start + block_size..];
+
+    // Decrypting the real last block gives the zero padded last cleartext
+    // xored with the real second-to-last block.  Where the cleartext was
+    // padding, that's the part of the second-to-last block that was cut off.
+    let mut decrypted_last = last.to_vec();
+    cipher.decrypt_block(&mut decrypted_last);
+    let mut second_last = stolen.to_vec();
+    second_last.extend_from_slice(&decrypted_last[last_len..]);
+
+    // put the ciphertext back the way plain CBC would have left it
+    let mut unstolen = ciphertext[..second_last_start].to_vec();
+    unstolen.extend(second_last);
+    unstolen.extend_from_slice(last);
+    let mut cleartext = try!(decrypt_cbc_with(cipher, &unstolen, iv));
+    cleartext.truncate(len);
+    Ok(cleartext)
+}
+
+fn check_iv<C: BlockCipher>(cipher: &C, iv: &[u8]) -> Result<(), CipherError> {
+    if iv.len() != cipher.block_size() {
+        Err(CipherError::InvalidLength)
+    } else {
+        Ok(())
+    }
+}
+
+// OFB keeps encrypting the IV to make a keystream, so like CTR it's its own
+// inverse and works on any length.  A flipped ciphertext bit flips just that
+// cleartext bit.
+pub fn decrypt_ofb_with<C: BlockCipher>(cipher: &C,
+                                        ciphertext: &[u8],
+                                        iv: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    try!(check_iv(cipher, iv));
     let mut cleartext = Vec::with_capacity(ciphertext.len());
-    let mut counter = [0u8; BLOCK_SIZE];
-    serialize_little_endian(nonce, &mut counter[0..8]);
-    for (i, block) in ciphertext.chunks(BLOCK_SIZE).enumerate() {
-        serialize_little_endian(&(i as u64), &mut counter[8..]);
-        let encrypted_counter = encrypt_ecb(&counter, &key).unwrap();
-        cleartext.append(&mut repeat_xor(block, &encrypted_counter));
+    let mut keystream = iv.to_vec();
+    for block in ciphertext.chunks(cipher.block_size()) {
+        cipher.encrypt_block(&mut keystream);
+        cleartext.append(&mut repeat_xor(block, &keystream));
     }
+    Ok(cleartext)
+}
 
+pub fn encrypt_ofb_with<C: BlockCipher>(cipher: &C,
+                                        cleartext: &[u8],
+                                        iv: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    decrypt_ofb_with(cipher, cleartext, iv)
+}
+
+// Full block CFB: the keystream for each block is the encryption of the
+// previous ciphertext block.  A flipped bit flips the same cleartext bit and
+// garbles the whole of the next block.  The last block can be partial.
+pub fn encrypt_cfb_with<C: BlockCipher>(cipher: &C,
+                                        cleartext: &[u8],
+                                        iv: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    try!(check_iv(cipher, iv));
+    let mut ciphertext = Vec::with_capacity(cleartext.len());
+    let mut feedback = iv.to_vec();
+    for block in cleartext.chunks(cipher.block_size()) {
+        cipher.encrypt_block(&mut feedback);
+        let encrypted = repeat_xor(block, &feedback);
+        ciphertext.extend_from_slice(&encrypted);
+        feedback = encrypted;
+    }
+    Ok(ciphertext)
+}
+
+pub fn decrypt_cfb_with<C: BlockCipher>(cipher: &C,
+                                        ciphertext: &[u8],
+                                        iv: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    try!(check_iv(cipher, iv));
+    let mut cleartext = Vec::with_capacity(ciphertext.len());
+    let mut feedback = iv.to_vec();
+    for block in ciphertext.chunks(cipher.block_size()) {
+        cipher.encrypt_block(&mut feedback);
+        cleartext.append(&mut repeat_xor(block, &feedback));
+        feedback = block.to_vec();
+    }
     Ok(cleartext)
 }
 
-// it's just the reverse of decrypt_ctr
-pub fn encrypt_ctr(cleartext: &[u8],
-                   key: &[u8],
-                   nonce: &u64)
-                   -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
-    decrypt_ctr(cleartext, key, nonce)
+// CFB8 shifts each ciphertext byte into a block-sized register and encrypts
+// it again for every byte, so it's block_size times slower than CFB128.  A
+// flipped bit garbles the following block_size bytes, until it's shifted out.
+fn cfb8_with<C: BlockCipher>(cipher: &C,
+                             data: &[u8],
+                             iv: &[u8],
+                             encrypting: bool)
+                             -> Result<Vec<u8>, CipherError> {
+    try!(check_iv(cipher, iv));
+    let mut output = Vec::with_capacity(data.len());
+    let mut register = iv.to_vec();
+    for b in data {
+        let mut keystream = register.clone();
+        cipher.encrypt_block(&mut keystream);
+        let out = b ^ keystream[0];
+        output.push(out);
+        register.remove(0);
+        register.push(if encrypting { out } else { *b });
+    }
+    Ok(output)
+}
+
+pub fn encrypt_cfb8_with<C: BlockCipher>(cipher: &C,
+                                         cleartext: &[u8],
+                                         iv: &[u8])
+                                         -> Result<Vec<u8>, CipherError> {
+    cfb8_with(cipher, cleartext, iv, true)
+}
+
+pub fn decrypt_cfb8_with<C: BlockCipher>(cipher: &C,
+                                         ciphertext: &[u8],
+                                         iv: &[u8])
+                                         -> Result<Vec<u8>, CipherError> {
+    cfb8_with(cipher, ciphertext, iv, false)
+}
+
+// PCBC is CBC with the previous cleartext block xored in as well as the
+// previous ciphertext block, so a single flipped bit garbles every block
+// after it.  (Swapping two adjacent ciphertext blocks doesn't, which is what
+// broke Kerberos 4.)  Whole blocks only.
+pub fn encrypt_pcbc_with<C: BlockCipher>(cipher: &C,
+                                         cleartext: &[u8],
+                                         iv: &[u8])
+                                         -> Result<Vec<u8>, CipherError> {
+    try!(check_iv(cipher, iv));
+    let block_size = cipher.block_size();
+    if cleartext.len() % block_size != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+    let mut ciphertext = Vec::with_capacity(cleartext.len());
+    let mut feedback = iv.to_vec();
+    for c in cleartext.chunks(block_size) {
+        let mut block = repeat_xor(c, &feedback);
+        cipher.encrypt_block(&mut block);
+        ciphertext.extend_from_slice(&block);
+        feedback = inplace_xor(block, c);
+    }
+    Ok(ciphertext)
+}
+
+pub fn decrypt_pcbc_with<C: BlockCipher>(cipher: &C,
+                                         ciphertext: &[u8],
+                                         iv: &[u8])
+                                         -> Result<Vec<u8>, CipherError> {
+    try!(check_iv(cipher, iv));
+    let block_size = cipher.block_size();
+    if ciphertext.len() % block_size != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+    let mut cleartext = Vec::with_capacity(ciphertext.len());
+    let mut feedback = iv.to_vec();
+    for c in ciphertext.chunks(block_size) {
+        let mut block = c.to_vec();
+        cipher.decrypt_block(&mut block);
+        block = inplace_xor(block, &feedback);
+        cleartext.extend_from_slice(&block);
+        feedback = inplace_xor(block, c);
+    }
+    Ok(cleartext)
+}
+
+// write the low dest.len() bytes of val
+fn serialize_little_endian(val: &u64, dest: &mut [u8]) {
+    for (i, d) in dest.iter_mut().enumerate() {
+        *d = ((val >> (8 * i)) & 0xffu64) as u8;
+    }
+}
+
+// The counter block is the nonce in the first half and the block count in the
+// second, both little endian.  For AES that's the 64/64 split the challenges
+// use; smaller blocks just get a truncated nonce and counter.
+pub fn fill_keystream_block<C: BlockCipher>(cipher: &C,
+                                            nonce: &u64,
+                                            block_num: u64,
+                                            dest: &mut [u8]) {
+    let half = dest.len() / 2;
+    serialize_little_endian(nonce, &mut dest[0..half]);
+    nonce_layout(dest.len()).write_counter(block_num as u128, &mut dest[half..]);
+    cipher.encrypt_block(dest);
 }
 
+// the layout fill_keystream_block makes, its truncated counter wrapping around
+fn nonce_layout(block_size: usize) -> CtrLayout {
+    CtrLayout {
+        counter_len: block_size - block_size / 2,
+        endianness: Endianness::Little,
+        position: CounterPosition::Trailing,
+        wraps: true,
+    }
+}
+
+pub fn decrypt_ctr_with<C: BlockCipher>(cipher: &C,
+                                        ciphertext: &[u8],
+                                        nonce: &u64)
+                                        -> Result<Vec<u8>, CipherError> {
+    let mut cleartext = ciphertext.to_vec();
+    apply_ctr_in_place_with(cipher, &mut cleartext, nonce);
+    Ok(cleartext)
+}
+
+// encrypts or decrypts, the same as decrypt_ctr_with
+pub fn apply_ctr_in_place_with<C: BlockCipher>(cipher: &C, data: &mut [u8], nonce: &u64) {
+    apply_ctr_keystream_at(cipher, nonce, 0, data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+// which end of the counter block the counter is at
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum CounterPosition {
+    Leading,
+    Trailing,
+}
+
+// How a CTR counter block is laid out: `counter_len` bytes at one end are a
+// counter that increments once per block, and the rest of the block is a
+// fixed nonce.  Unless it `wraps`, running out of counter is an error rather
+// than a return to keystream that's already been used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub struct CtrLayout {
+    pub counter_len: usize,
+    pub endianness: Endianness,
+    pub position: CounterPosition,
+    pub wraps: bool,
+}
+
+impl CtrLayout {
+    // 64 bit nonce then a 64 bit little endian counter - the challenges' layout
+    #[allow(dead_code)]
+    pub fn little_endian_64() -> CtrLayout {
+        CtrLayout {
+            counter_len: 8,
+            endianness: Endianness::Little,
+            position: CounterPosition::Trailing,
+            wraps: false,
+        }
+    }
+
+    // 96 bit nonce then a 32 bit big endian counter, as in RFC 3686
+    #[allow(dead_code)]
+    pub fn nonce_96() -> CtrLayout {
+        CtrLayout {
+            counter_len: 4,
+            endianness: Endianness::Big,
+            position: CounterPosition::Trailing,
+            wraps: false,
+        }
+    }
+
+    // GCM's inc32: the same, but the counter wraps mod 2^32 and leaves the
+    // rest of the block alone
+    pub fn inc32() -> CtrLayout {
+        CtrLayout { wraps: true, ..CtrLayout::nonce_96() }
+    }
+
+    // the whole block is one big endian counter starting at the IV, as in
+    // SP 800-38A
+    #[allow(dead_code)]
+    pub fn full_block() -> CtrLayout {
+        CtrLayout {
+            counter_len: 16,
+            endianness: Endianness::Big,
+            position: CounterPosition::Trailing,
+            wraps: false,
+        }
+    }
+
+    fn read_counter(&self, counter: &[u8]) -> u128 {
+        let mut val = 0u128;
+        for i in 0..counter.len() {
+            let b = match self.endianness {
+                Endianness::Big => counter[i],
+                Endianness::Little => counter[counter.len() - 1 - i],
+            };
+            val = (val << 8) | b as u128;
+        }
+        val
+    }
+
+    fn write_counter(&self, val: u128, counter: &mut [u8]) {
+        let len = counter.len();
+        for i in 0..len {
+            let b = (val >> (8 * i)) as u8;
+            match self.endianness {
+                Endianness::Big => counter[len - 1 - i] = b,
+                Endianness::Little => counter[i] = b,
+            }
+        }
+    }
+
+    // The counter block for block number `block_num`, counting from
+    // `initial_block`.
+    #[allow(dead_code)]
+    pub fn counter_block(&self,
+                         initial_block: &[u8],
+                         block_num: u64)
+                         -> Result<Vec<u8>, CipherError> {
+        let mut block = vec![0u8; initial_block.len()];
+        try!(self.fill_counter_block(initial_block, block_num, &mut block));
+        Ok(block)
+    }
+
+    // counter_block into `dest`, which must be as long as `initial_block`
+    fn fill_counter_block(&self,
+                          initial_block: &[u8],
+                          block_num: u64,
+                          dest: &mut [u8])
+                          -> Result<(), CipherError> {
+        if self.counter_len == 0 || self.counter_len > initial_block.len() ||
+           self.counter_len > 16 || dest.len() != initial_block.len() {
+            return Err(CipherError::InvalidLength);
+        }
+
+        let counter_start = match self.position {
+            CounterPosition::Leading => 0,
+            CounterPosition::Trailing => initial_block.len() - self.counter_len,
+        };
+        let counter_end = counter_start + self.counter_len;
+        let initial = self.read_counter(&initial_block[counter_start..counter_end]);
+        let counter = if self.wraps {
+            // write_counter drops whatever carries out of the top
+            initial.wrapping_add(block_num as u128)
+        } else {
+            let counter = try!(initial
+                                   .checked_add(block_num as u128)
+                                   .ok_or(CipherError::CounterOverflow));
+            if self.counter_len < 16 && counter >> (8 * self.counter_len) != 0 {
+                return Err(CipherError::CounterOverflow);
+            }
+            counter
+        };
+
+        dest.copy_from_slice(initial_block);
+        self.write_counter(counter, &mut dest[counter_start..counter_end]);
+        Ok(())
+    }
+}
+
+// CTR with an explicit counter layout.  `initial_block` is the whole first
+// counter block (nonce and starting counter).
+#[allow(dead_code)]
+pub fn apply_ctr_with_layout<C: BlockCipher>(cipher: &C,
+                                             layout: &CtrLayout,
+                                             initial_block: &[u8],
+                                             data: &[u8])
+                                             -> Result<Vec<u8>, CipherError> {
+    let mut output = data.to_vec();
+    try!(apply_ctr_layout_at(cipher, layout, initial_block, 0, &mut output));
+    Ok(output)
+}
+
+#[allow(dead_code)]
+pub fn encrypt_ctr_with_layout(cleartext: &[u8],
+                               key: &[u8],
+                               layout: &CtrLayout,
+                               initial_block: &[u8])
+                               -> Result<Vec<u8>, CipherError> {
+    apply_ctr_with_layout(&try!(Aes::new(key)), layout, initial_block, cleartext)
+}
+
+#[allow(dead_code)]
+pub fn decrypt_ctr_with_layout(ciphertext: &[u8],
+                               key: &[u8],
+                               layout: &CtrLayout,
+                               initial_block: &[u8])
+                               -> Result<Vec<u8>, CipherError> {
+    encrypt_ctr_with_layout(ciphertext, key, layout, initial_block)
+}
+
+// xor the keystream starting at byte `offset` (rather than at the start of
+// the message) into data
+pub fn apply_ctr_keystream_at<C: BlockCipher>(cipher: &C,
+                                              nonce: &u64,
+                                              offset: u64,
+                                              data: &mut [u8]) {
+    let block_size = cipher.block_size();
+    let mut initial_block = vec![0u8; block_size];
+    serialize_little_endian(nonce, &mut initial_block[..block_size / 2]);
+    // the counter wraps, so there's nothing to fail
+    apply_ctr_layout_at(cipher, &nonce_layout(block_size), &initial_block, offset, data).unwrap();
+}
+
+// apply_ctr_keystream_at with an explicit counter layout.  Checks the counter
+// won't run out before doing any work.
+pub fn apply_ctr_layout_at<C: BlockCipher>(cipher: &C,
+                                           layout: &CtrLayout,
+                                           initial_block: &[u8],
+                                           offset: u64,
+                                           data: &mut [u8])
+                                           -> Result<(), CipherError> {
+    let block_size = cipher.block_size();
+    if initial_block.len() != block_size {
+        return Err(CipherError::InvalidLength);
+    }
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    let mut keystream = vec![0u8; block_size];
+    let last_block = (offset + data.len() as u64 - 1) / block_size as u64;
+    try!(layout.fill_counter_block(initial_block, last_block, &mut keystream));
+
+    let mut block_num = offset / block_size as u64;
+    try!(layout.fill_counter_block(initial_block, block_num, &mut keystream));
+    cipher.encrypt_block(&mut keystream);
+    let mut pos = (offset % block_size as u64) as usize;
+    for b in data.iter_mut() {
+        if pos == block_size {
+            block_num += 1;
+            try!(layout.fill_counter_block(initial_block, block_num, &mut keystream));
+            cipher.encrypt_block(&mut keystream);
+            pos = 0;
+        }
+        *b ^= keystream[pos];
+        pos += 1;
+    }
+    Ok(())
+}
+
+// Replace the cleartext at `offset` with newtext, re-encrypting in place
+// without touching the rest of the ciphertext.
+pub fn edit(ciphertext: &mut [u8],
+            key: &[u8],
+            nonce: &u64,
+            offset: usize,
+            newtext: &[u8])
+            -> Result<(), CipherError> {
+    // checked, since an offset near usize::MAX would wrap past the test
+    if offset.checked_add(newtext.len()).map_or(true, |end| end > ciphertext.len()) {
+        return Err(CipherError::InvalidLength);
+    }
+    let aes = try!(Aes::new(key));
+    let target = &mut ciphertext[offset..offset + newtext.len()];
+    target.copy_from_slice(newtext);
+    apply_ctr_keystream_at(&aes, nonce, offset as u64, target);
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn encrypt_ctr_with<C: BlockCipher>(cipher: &C,
+                                        cleartext: &[u8],
+                                        nonce: &u64)
+                                        -> Result<Vec<u8>, CipherError> {
+    decrypt_ctr_with(cipher, cleartext, nonce)
+}
 
 #[test]
 fn test() {
@@ -243,3 +838,402 @@ fn test_ctr() {
         }
     }
 }
+
+#[cfg(test)]
+static SP800_38A_PLAINTEXT: &'static str = "6bc1bee22e409f96e93d7e117393172a\
+                                             ae2d8a571e03ac9c9eb76fac45af8e51\
+                                             30c81c46a35ce411e5fbc1191a0a52ef\
+                                             f69f2445df4f9b17ad2b417be66c3710";
+
+#[cfg(test)]
+static SP800_38A_KEYS: [&'static str; 3] =
+    ["2b7e151628aed2a6abf7158809cf4f3c",
+     "8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b",
+     "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4"];
+
+#[test]
+fn test_sp800_38a_ecb() {
+    use hexstring::{fromhex, tohex};
+
+    let expected = ["3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf\
+                     43b1cd7f598ece23881b00e3ed0306887b0c785e27e8ad3f8223207104725dd4",
+                    "bd334f1d6e45f25ff712a214571fa5cc974104846d0ad3ad7734ecb3ecee4eef\
+                     ef7afd2270e2e60adce0ba2face6444e9a4b41ba738d6c72fb16691603c18e0e",
+                    "f3eed1bdb5d2a03c064b5a7e3db181f8591ccb10d410ed26dc5ba74a31362870\
+                     b6ed21b99ca6f4f9f153e7b1beafed1d23304b7a39f9f3ff067d8d8f9e24ecc7"];
+    let plaintext = fromhex(SP800_38A_PLAINTEXT).unwrap();
+    for (key, ciphertext) in SP800_38A_KEYS.iter().zip(expected.iter()) {
+        let key = fromhex(key).unwrap();
+        let encrypted = encrypt_ecb(&plaintext, &key).unwrap();
+        assert_eq!(&tohex(&encrypted), ciphertext);
+        assert_eq!(decrypt_ecb(&encrypted, &key).unwrap(), plaintext);
+    }
+}
+
+#[test]
+fn test_sp800_38a_cbc() {
+    use hexstring::{fromhex, tohex};
+
+    let expected = ["7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2\
+                     73bed6b8e3c1743b7116e69e222295163ff1caa1681fac09120eca307586e1a7",
+                    "4f021db243bc633d7178183a9fa071e8b4d9ada9ad7dedf4e5e738763f69145a\
+                     571b242012fb7ae07fa9baac3df102e008b0e27988598881d920a9e64f5615cd",
+                    "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d\
+                     39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b"];
+    let plaintext = fromhex(SP800_38A_PLAINTEXT).unwrap();
+    let iv = fromhex("000102030405060708090a0b0c0d0e0f").unwrap();
+    for (key, ciphertext) in SP800_38A_KEYS.iter().zip(expected.iter()) {
+        let key = fromhex(key).unwrap();
+        let encrypted = encrypt_cbc(&plaintext, &key, &iv).unwrap();
+        assert_eq!(&tohex(&encrypted), ciphertext);
+        assert_eq!(decrypt_cbc(&encrypted, &key, &iv).unwrap(), plaintext);
+    }
+}
+
+#[test]
+fn test_sp800_38a_ofb_cfb() {
+    use hexstring::{fromhex, tohex};
+
+    let ofb = ["3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+                9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e",
+               "cdc80d6fddf18cab34c25909c99a4174fcc28b8d4c63837c09e81700c1100401\
+                8d9a9aeac0f6596f559c6d4daf59a5f26d9f200857ca6c3e9cac524bd9acc92a",
+               "dc7e84bfda79164b7ecd8486985d38604febdc6740d20b3ac88f6ad82a4fb08d\
+                71ab47a086e86eedf39d1c5bba97c4080126141d67f37be8538f5a8be740e484"];
+    let cfb128 = ["3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+                   26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6",
+                  "cdc80d6fddf18cab34c25909c99a417467ce7f7f81173621961a2b70171d3d7a\
+                   2e1e8a1dd59b88b1c8e60fed1efac4c9c05f9f9ca9834fa042ae8fba584b09ff",
+                  "dc7e84bfda79164b7ecd8486985d386039ffed143b28b1c832113c6331e5407b\
+                   df10132415e54b92a13ed0a8267ae2f975a385741ab9cef82031623d55b1e471"];
+    // the CFB8 vectors only use the first 18 bytes
+    let cfb8 = ["3b79424c9c0dd436bace9e0ed4586a4f32b9",
+                "cda2521ef0a905ca44cd057cbf0d47a0678a",
+                "dc1f1a8520a64db55fcc8ac554844e889700"];
+    let plaintext = fromhex(SP800_38A_PLAINTEXT).unwrap();
+    let iv = fromhex("000102030405060708090a0b0c0d0e0f").unwrap();
+    for (i, key) in SP800_38A_KEYS.iter().enumerate() {
+        let key = fromhex(key).unwrap();
+
+        let encrypted = encrypt_ofb(&plaintext, &key, &iv).unwrap();
+        assert_eq!(tohex(&encrypted), ofb[i]);
+        assert_eq!(decrypt_ofb(&encrypted, &key, &iv).unwrap(), plaintext);
+
+        let encrypted = encrypt_cfb128(&plaintext, &key, &iv).unwrap();
+        assert_eq!(tohex(&encrypted), cfb128[i]);
+        assert_eq!(decrypt_cfb128(&encrypted, &key, &iv).unwrap(), plaintext);
+
+        let encrypted = encrypt_cfb8(&plaintext[..18], &key, &iv).unwrap();
+        assert_eq!(tohex(&encrypted), cfb8[i]);
+        assert_eq!(decrypt_cfb8(&encrypted, &key, &iv).unwrap(), &plaintext[..18]);
+    }
+}
+
+#[test]
+fn test_feedback_modes() {
+    use bytes::random_bytes;
+
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    for len in 0..50 {
+        let cleartext = random_bytes(len);
+        let ciphertext = encrypt_ofb(&cleartext, &key, &iv).unwrap();
+        assert_eq!(decrypt_ofb(&ciphertext, &key, &iv).unwrap(), cleartext);
+        let ciphertext = encrypt_cfb128(&cleartext, &key, &iv).unwrap();
+        assert_eq!(decrypt_cfb128(&ciphertext, &key, &iv).unwrap(), cleartext);
+        let ciphertext = encrypt_cfb8(&cleartext, &key, &iv).unwrap();
+        assert_eq!(decrypt_cfb8(&ciphertext, &key, &iv).unwrap(), cleartext);
+    }
+
+    for num_blocks in 0..5 {
+        let cleartext = random_bytes(num_blocks * 16);
+        let ciphertext = encrypt_pcbc(&cleartext, &key, &iv).unwrap();
+        assert_eq!(decrypt_pcbc(&ciphertext, &key, &iv).unwrap(), cleartext);
+    }
+    assert_eq!(encrypt_pcbc(&[0u8; 17], &key, &iv), Err(CipherError::InvalidLength));
+    assert_eq!(encrypt_ofb(&[0u8; 16], &key, &iv[..8]), Err(CipherError::InvalidLength));
+
+    // the first PCBC block is just CBC
+    let cleartext = random_bytes(32);
+    assert_eq!(encrypt_pcbc(&cleartext, &key, &iv).unwrap()[..16],
+               encrypt_cbc(&cleartext, &key, &iv).unwrap()[..16]);
+}
+
+#[test]
+fn test_in_place() {
+    use bytes::random_bytes;
+
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    for num_blocks in 0..6 {
+        let cleartext = random_bytes(num_blocks * 16);
+        let mut data = cleartext.clone();
+        encrypt_cbc_in_place(&mut data, &key, &iv).unwrap();
+        assert_eq!(data, encrypt_cbc(&cleartext, &key, &iv).unwrap());
+        decrypt_cbc_in_place(&mut data, &key, &iv).unwrap();
+        assert_eq!(data, cleartext);
+
+        let cleartext = random_bytes(num_blocks * 7);
+        let mut data = cleartext.clone();
+        apply_ctr_in_place(&mut data, &key, &3).unwrap();
+        assert_eq!(data, encrypt_ctr(&cleartext, &key, &3).unwrap());
+        apply_ctr_in_place(&mut data, &key, &3).unwrap();
+        assert_eq!(data, cleartext);
+    }
+    assert_eq!(encrypt_cbc_in_place(&mut [0u8; 17], &key, &iv),
+               Err(CipherError::InvalidLength));
+
+    let mut block = [0u8; 16];
+    encrypt_block_in_place(&mut block, &key).unwrap();
+    assert_eq!(block.to_vec(), encrypt_ecb(&[0u8; 16], &key).unwrap());
+    decrypt_block_in_place(&mut block, &key).unwrap();
+    assert_eq!(block, [0u8; 16]);
+    assert_eq!(encrypt_block_in_place(&mut [0u8; 8], &key), Err(CipherError::InvalidLength));
+}
+
+#[test]
+fn test_key_sizes() {
+    use bytes::random_bytes;
+
+    let cleartext = random_bytes(100);
+    for keysize in [16, 24, 32].iter() {
+        let key = random_bytes(*keysize);
+        let ciphertext = encrypt_ctr(&cleartext, &key, &7).unwrap();
+        assert_eq!(decrypt_ctr(&ciphertext, &key, &7).unwrap(), cleartext);
+    }
+
+    assert_eq!(encrypt_ecb(&[0u8; 16], &[0u8; 20]), Err(CipherError::InvalidKeyLength));
+    assert_eq!(encrypt_cbc(&[0u8; 16], &[0u8; 32], &[0u8; 32]), Err(CipherError::InvalidLength));
+}
+
+#[test]
+fn test_small_blocks() {
+    use blockcipher::ToyCipher;
+    use bytes::random_bytes;
+
+    for block_size in [2, 4, 8].iter() {
+        let cipher = ToyCipher::new(b"key", *block_size).unwrap();
+        let cleartext = random_bytes(block_size * 10);
+        let iv = random_bytes(*block_size);
+
+        let ecb = encrypt_ecb_with(&cipher, &cleartext).unwrap();
+        assert_eq!(decrypt_ecb_with(&cipher, &ecb).unwrap(), cleartext);
+
+        let cbc = encrypt_cbc_with(&cipher, &cleartext, &iv).unwrap();
+        assert_eq!(decrypt_cbc_with(&cipher, &cbc, &iv).unwrap(), cleartext);
+
+        let ctr = encrypt_ctr_with(&cipher, &cleartext[1..], &3).unwrap();
+        assert_eq!(decrypt_ctr_with(&cipher, &ctr, &3).unwrap(), &cleartext[1..]);
+    }
+}
+
+#[test]
+fn test_ctr_edit() {
+    use bytes::random_bytes;
+
+    let key = random_bytes(16);
+    let cleartext = random_bytes(100);
+    for offset in 0..100 {
+        for len in 0..(100 - offset) {
+            let newtext = random_bytes(len);
+            let mut ciphertext = encrypt_ctr(&cleartext, &key, &9).unwrap();
+            edit(&mut ciphertext, &key, &9, offset, &newtext).unwrap();
+
+            let mut expected = cleartext.clone();
+            expected[offset..offset + len].copy_from_slice(&newtext);
+            assert_eq!(decrypt_ctr(&ciphertext, &key, &9).unwrap(), expected);
+        }
+    }
+
+    let mut ciphertext = vec![0u8; 10];
+    assert_eq!(edit(&mut ciphertext, &key, &9, 8, b"abc"), Err(CipherError::InvalidLength));
+    // offset + len would wrap round to 1
+    assert_eq!(edit(&mut ciphertext, &key, &9, usize::max_value(), b"ab"),
+               Err(CipherError::InvalidLength));
+}
+
+#[test]
+fn test_sp800_38a_ctr() {
+    use hexstring::{fromhex, tohex};
+
+    let expected = ["874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff\
+                     5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee",
+                    "1abc932417521ca24f2b0459fe7e6e0b090339ec0aa6faefd5ccc2c6f4ce8e94\
+                     1e36b26bd1ebc670d1bd1d665620abf74f78a7f6d29809585a97daec58c6b050",
+                    "601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5\
+                     2b0930daa23de94ce87017ba2d84988ddfc9c58db67aada613c2dd08457941a6"];
+    let plaintext = fromhex(SP800_38A_PLAINTEXT).unwrap();
+    let counter = fromhex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap();
+    let layout = CtrLayout::full_block();
+    for (key, ciphertext) in SP800_38A_KEYS.iter().zip(expected.iter()) {
+        let key = fromhex(key).unwrap();
+        let encrypted = encrypt_ctr_with_layout(&plaintext, &key, &layout, &counter).unwrap();
+        assert_eq!(&tohex(&encrypted), ciphertext);
+        assert_eq!(decrypt_ctr_with_layout(&encrypted, &key, &layout, &counter).unwrap(),
+                   plaintext);
+    }
+}
+
+#[test]
+fn test_ctr_layouts() {
+    use hexstring::{fromhex, tohex};
+    use bytes::random_bytes;
+
+    // RFC 3686 test vector #1 - nonce, IV, then a big endian counter from 1
+    let key = fromhex("ae6852f8121067cc4bf7a5765577f39e").unwrap();
+    let counter = fromhex("00000030000000000000000000000001").unwrap();
+    let layout = CtrLayout::nonce_96();
+    let ciphertext = encrypt_ctr_with_layout(b"Single block msg", &key, &layout, &counter).unwrap();
+    assert_eq!(tohex(&ciphertext), "e4095d4fb7a7b3792d6175a3261311b8");
+
+    // the challenges' layout is the original decrypt_ctr
+    let cleartext = random_bytes(100);
+    let mut counter = [0u8; 16];
+    counter[0] = 5;
+    let layout = CtrLayout::little_endian_64();
+    assert_eq!(encrypt_ctr_with_layout(&cleartext, &key, &layout, &counter).unwrap(),
+               encrypt_ctr(&cleartext, &key, &5).unwrap());
+
+    // a big endian counter in front of the nonce, carrying from its second byte
+    let layout = CtrLayout {
+        counter_len: 2,
+        endianness: Endianness::Big,
+        position: CounterPosition::Leading,
+        wraps: false,
+    };
+    let mut counter = [0x77u8; 16];
+    counter[0] = 0x12;
+    counter[1] = 0xff;
+    let keystream = encrypt_ctr_with_layout(&[0u8; 32], &key, &layout, &counter).unwrap();
+    let mut next = counter;
+    next[0] = 0x13;
+    next[1] = 0x00;
+    assert_eq!(keystream, encrypt_ecb(&[&counter[..], &next[..]].concat(), &key).unwrap());
+}
+
+#[test]
+fn test_ctr_overflow() {
+    let key = [0u8; 16];
+
+    // a 32 bit counter at its last value has room for exactly one block
+    let counter = [0xffu8; 16];
+    let layout = CtrLayout::nonce_96();
+    assert!(encrypt_ctr_with_layout(&[0u8; 16], &key, &layout, &counter).is_ok());
+    assert_eq!(encrypt_ctr_with_layout(&[0u8; 17], &key, &layout, &counter),
+               Err(CipherError::CounterOverflow));
+
+    let layout = CtrLayout::full_block();
+    assert_eq!(encrypt_ctr_with_layout(&[0u8; 32], &key, &layout, &counter),
+               Err(CipherError::CounterOverflow));
+
+    let layout = CtrLayout {
+        counter_len: 2,
+        endianness: Endianness::Little,
+        position: CounterPosition::Trailing,
+        wraps: false,
+    };
+    let mut counter = [0u8; 16];
+    counter[14] = 0xfe;
+    counter[15] = 0xff;
+    assert!(encrypt_ctr_with_layout(&[0u8; 32], &key, &layout, &counter).is_ok());
+    assert_eq!(encrypt_ctr_with_layout(&[0u8; 33], &key, &layout, &counter),
+               Err(CipherError::CounterOverflow));
+
+    // inc32 goes round to zero instead, without carrying into the nonce
+    let counter = [0xffu8; 16];
+    let keystream = encrypt_ctr_with_layout(&[0u8; 32], &key, &CtrLayout::inc32(), &counter)
+        .unwrap();
+    let mut next = counter;
+    next[12..].copy_from_slice(&[0; 4]);
+    assert_eq!(keystream, encrypt_ecb(&[&counter[..], &next[..]].concat(), &key).unwrap());
+
+    // a leading counter overflows just the same, whatever's after it
+    let layout = CtrLayout { position: CounterPosition::Leading, ..layout };
+    let mut counter = [0u8; 16];
+    counter[0] = 0xfe;
+    counter[1] = 0xff;
+    assert!(encrypt_ctr_with_layout(&[0u8; 32], &key, &layout, &counter).is_ok());
+    assert_eq!(encrypt_ctr_with_layout(&[0u8; 33], &key, &layout, &counter),
+               Err(CipherError::CounterOverflow));
+}
+
+#[test]
+fn test_padded() {
+    use bytes::random_bytes;
+
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    for len in 0..50 {
+        let cleartext = random_bytes(len);
+
+        let ciphertext = encrypt_cbc_padded(&cleartext, &key, &iv).unwrap();
+        assert_eq!(ciphertext.len(), (len / 16 + 1) * 16);
+        assert_eq!(decrypt_cbc_padded(&ciphertext, &key, &iv).unwrap(), cleartext);
+
+        let ciphertext = encrypt_ecb_padded(&cleartext, &key).unwrap();
+        assert_eq!(decrypt_ecb_padded(&ciphertext, &key).unwrap(), cleartext);
+    }
+
+    // no padding at all
+    let ciphertext = encrypt_cbc(&[0u8; 16], &key, &iv).unwrap();
+    assert_eq!(decrypt_cbc_padded(&ciphertext, &key, &iv),
+               Err(CipherError::Padding(PaddingError::BadPaddingLength(0))));
+    assert_eq!(decrypt_cbc_padded(&ciphertext[1..], &key, &iv),
+               Err(CipherError::Padding(PaddingError::NotBlockAligned)));
+    assert_eq!(decrypt_cbc_padded(&[], &key, &iv),
+               Err(CipherError::Padding(PaddingError::Empty)));
+}
+
+#[test]
+fn test_padding_schemes() {
+    use bytes::random_bytes;
+    use padding::{AnsiX923, Iso10126, Iso7816};
+
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    let cleartext = random_bytes(37);
+    let ciphertext = encrypt_cbc_with_padding(&cleartext, &key, &iv, &Iso7816).unwrap();
+    assert_eq!(decrypt_cbc_with_padding(&ciphertext, &key, &iv, &Iso7816).unwrap(),
+               cleartext);
+    let ciphertext = encrypt_ecb_with_padding(&cleartext, &key, &Iso10126).unwrap();
+    assert_eq!(decrypt_ecb_with_padding(&ciphertext, &key, &Iso10126).unwrap(),
+               cleartext);
+
+    // X9.23 padding is zeros, so pkcs7 will (usually) reject it
+    let ciphertext = encrypt_cbc_with_padding(b"12345", &key, &iv, &AnsiX923).unwrap();
+    assert_eq!(decrypt_cbc_with_padding(&ciphertext, &key, &iv, &AnsiX923).unwrap(),
+               b"12345");
+    assert_eq!(decrypt_cbc_padded(&ciphertext, &key, &iv),
+               Err(CipherError::Padding(PaddingError::BadPaddingByte)));
+}
+
+#[test]
+fn test_cts() {
+    use hexstring::{fromhex, tohex};
+    use bytes::random_bytes;
+
+    // RFC 3962 appendix B
+    let key = b"chicken teriyaki";
+    let iv = [0u8; 16];
+    let cleartext = b"I would like the General Gau's Chicken, please, and wonton soup.";
+    let expected = [(17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+                    (31, "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5"),
+                    (32, "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584"),
+                    (47,
+                     "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e\
+                      39312523a78662d5be7fcbcc98ebf5")];
+    for &(len, ciphertext) in expected.iter() {
+        let encrypted = encrypt_cbc_cts(&cleartext[..len], key, &iv).unwrap();
+        assert_eq!(tohex(&encrypted), ciphertext);
+        assert_eq!(decrypt_cbc_cts(&fromhex(ciphertext).unwrap(), key, &iv).unwrap(),
+                   &cleartext[..len]);
+    }
+
+    for len in 16..80 {
+        let cleartext = random_bytes(len);
+        let ciphertext = encrypt_cbc_cts(&cleartext, key, &iv).unwrap();
+        assert_eq!(ciphertext.len(), len);
+        assert_eq!(decrypt_cbc_cts(&ciphertext, key, &iv).unwrap(), cleartext);
+    }
+    assert_eq!(encrypt_cbc_cts(b"short", key, &iv), Err(CipherError::InvalidLength));
+}
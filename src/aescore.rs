@@ -0,0 +1,406 @@
+// A plain AES implementation, written to follow FIPS-197 as closely as
+// possible so that every step of every round can be looked at.
+
+use aesni;
+use bitslice;
+use blockcipher::{BlockCipher, CipherError};
+
+pub const BLOCK_SIZE: usize = 16;
+
+static SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+static INV_SBOX: [u8; 256] = [
+    0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
+    0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
+    0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
+    0x08, 0x2e, 0xa1, 0x66, 0x28, 0xd9, 0x24, 0xb2, 0x76, 0x5b, 0xa2, 0x49, 0x6d, 0x8b, 0xd1, 0x25,
+    0x72, 0xf8, 0xf6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xd4, 0xa4, 0x5c, 0xcc, 0x5d, 0x65, 0xb6, 0x92,
+    0x6c, 0x70, 0x48, 0x50, 0xfd, 0xed, 0xb9, 0xda, 0x5e, 0x15, 0x46, 0x57, 0xa7, 0x8d, 0x9d, 0x84,
+    0x90, 0xd8, 0xab, 0x00, 0x8c, 0xbc, 0xd3, 0x0a, 0xf7, 0xe4, 0x58, 0x05, 0xb8, 0xb3, 0x45, 0x06,
+    0xd0, 0x2c, 0x1e, 0x8f, 0xca, 0x3f, 0x0f, 0x02, 0xc1, 0xaf, 0xbd, 0x03, 0x01, 0x13, 0x8a, 0x6b,
+    0x3a, 0x91, 0x11, 0x41, 0x4f, 0x67, 0xdc, 0xea, 0x97, 0xf2, 0xcf, 0xce, 0xf0, 0xb4, 0xe6, 0x73,
+    0x96, 0xac, 0x74, 0x22, 0xe7, 0xad, 0x35, 0x85, 0xe2, 0xf9, 0x37, 0xe8, 0x1c, 0x75, 0xdf, 0x6e,
+    0x47, 0xf1, 0x1a, 0x71, 0x1d, 0x29, 0xc5, 0x89, 0x6f, 0xb7, 0x62, 0x0e, 0xaa, 0x18, 0xbe, 0x1b,
+    0xfc, 0x56, 0x3e, 0x4b, 0xc6, 0xd2, 0x79, 0x20, 0x9a, 0xdb, 0xc0, 0xfe, 0x78, 0xcd, 0x5a, 0xf4,
+    0x1f, 0xdd, 0xa8, 0x33, 0x88, 0x07, 0xc7, 0x31, 0xb1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xec, 0x5f,
+    0x60, 0x51, 0x7f, 0xa9, 0x19, 0xb5, 0x4a, 0x0d, 0x2d, 0xe5, 0x7a, 0x9f, 0x93, 0xc9, 0x9c, 0xef,
+    0xa0, 0xe0, 0x3b, 0x4d, 0xae, 0x2a, 0xf5, 0xb0, 0xc8, 0xeb, 0xbb, 0x3c, 0x83, 0x53, 0x99, 0x61,
+    0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d,
+];
+
+// round constants for the key schedule - successive powers of x in GF(2^8)
+static RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+// Which implementation encrypt_block and decrypt_block use.  The traced
+// versions are always portable, since AES-NI doesn't expose the state between
+// steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Portable,
+    AesNi,
+    Bitsliced,
+}
+
+pub struct Aes {
+    round_keys: Vec<[u8; BLOCK_SIZE]>,
+    backend: Backend,
+    // only used by the AES-NI backend
+    inverse_round_keys: Vec<[u8; BLOCK_SIZE]>,
+}
+
+impl Aes {
+    // uses AES-NI if the CPU has it
+    pub fn new(key: &[u8]) -> Result<Aes, CipherError> {
+        let mut aes = try!(Aes::new_portable(key));
+        if aesni::available() {
+            aes.inverse_round_keys = aesni::inverse_round_keys(&aes.round_keys);
+            aes.backend = Backend::AesNi;
+        }
+        Ok(aes)
+    }
+
+    pub fn new_portable(key: &[u8]) -> Result<Aes, CipherError> {
+        let round_keys = try!(expand_key(key));
+        Ok(Aes {
+               round_keys: round_keys,
+               backend: Backend::Portable,
+               inverse_round_keys: Vec::new(),
+           })
+    }
+
+    // constant time, and much slower
+    pub fn new_bitsliced(key: &[u8]) -> Result<Aes, CipherError> {
+        let round_keys = try!(bitslice::expand_key(key));
+        Ok(Aes {
+               round_keys: round_keys,
+               backend: Backend::Bitsliced,
+               inverse_round_keys: Vec::new(),
+           })
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    // 10, 12 or 14 depending on key size
+    pub fn rounds(&self) -> usize {
+        self.round_keys.len() - 1
+    }
+
+    #[allow(dead_code)]
+    pub fn round_key(&self, round: usize) -> &[u8; BLOCK_SIZE] {
+        &self.round_keys[round]
+    }
+
+    pub fn encrypt_block(&self, block: &mut [u8]) {
+        match self.backend {
+            // new() only picks AesNi when the CPU supports it
+            Backend::AesNi => unsafe { aesni::encrypt_block(&self.round_keys, block) },
+            Backend::Bitsliced => bitslice::encrypt_block(&self.round_keys, block),
+            Backend::Portable => self.encrypt_block_with_trace(block, |_, _| {}),
+        }
+    }
+
+    pub fn decrypt_block(&self, block: &mut [u8]) {
+        match self.backend {
+            Backend::AesNi => unsafe { aesni::decrypt_block(&self.inverse_round_keys, block) },
+            Backend::Bitsliced => bitslice::decrypt_block(&self.round_keys, block),
+            Backend::Portable => self.decrypt_block_with_trace(block, |_, _| {}),
+        }
+    }
+
+    // Encrypt one block, calling `trace` with the round number and the state
+    // at the start of each round (after the final round, the state is the
+    // ciphertext).
+    pub fn encrypt_block_with_trace<F>(&self, block: &mut [u8], mut trace: F)
+        where F: FnMut(usize, &[u8])
+    {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let nr = self.rounds();
+
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..nr {
+            trace(round, block);
+            sub_bytes(block);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+
+        trace(nr, block);
+        sub_bytes(block);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[nr]);
+        trace(nr + 1, block);
+    }
+
+    // The straightforward inverse cipher from section 5.3 of FIPS-197.
+    // `trace` is called with the round number counting down.
+    pub fn decrypt_block_with_trace<F>(&self, block: &mut [u8], mut trace: F)
+        where F: FnMut(usize, &[u8])
+    {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let nr = self.rounds();
+
+        add_round_key(block, &self.round_keys[nr]);
+        for round in (1..nr).rev() {
+            trace(round + 1, block);
+            inv_shift_rows(block);
+            inv_sub_bytes(block);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+
+        trace(1, block);
+        inv_shift_rows(block);
+        inv_sub_bytes(block);
+        add_round_key(block, &self.round_keys[0]);
+        trace(0, block);
+    }
+}
+
+impl BlockCipher for Aes {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        Aes::encrypt_block(self, block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        Aes::decrypt_block(self, block);
+    }
+}
+
+// multiply by x in GF(2^8), reducing by x^8 + x^4 + x^3 + x + 1
+pub fn xtime(b: u8) -> u8 {
+    let shifted = b << 1;
+    if b & 0x80 != 0 {
+        shifted ^ 0x1b
+    } else {
+        shifted
+    }
+}
+
+// general multiplication in GF(2^8)
+pub fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+pub fn sub_bytes(state: &mut [u8]) {
+    for b in state.iter_mut() {
+        *b = SBOX[*b as usize];
+    }
+}
+
+pub fn inv_sub_bytes(state: &mut [u8]) {
+    for b in state.iter_mut() {
+        *b = INV_SBOX[*b as usize];
+    }
+}
+
+// The state is stored column by column, so byte (row, col) is at
+// index row + 4 * col.  Row r is rotated left by r columns.
+pub fn shift_rows(state: &mut [u8]) {
+    let old = copy_block(state);
+    for row in 1..4 {
+        for col in 0..4 {
+            state[row + 4 * col] = old[row + 4 * ((col + row) % 4)];
+        }
+    }
+}
+
+pub fn inv_shift_rows(state: &mut [u8]) {
+    let old = copy_block(state);
+    for row in 1..4 {
+        for col in 0..4 {
+            state[row + 4 * ((col + row) % 4)] = old[row + 4 * col];
+        }
+    }
+}
+
+pub fn mix_columns(state: &mut [u8]) {
+    for column in state.chunks_mut(4) {
+        let (a0, a1, a2, a3) = (column[0], column[1], column[2], column[3]);
+        column[0] = xtime(a0) ^ (xtime(a1) ^ a1) ^ a2 ^ a3;
+        column[1] = a0 ^ xtime(a1) ^ (xtime(a2) ^ a2) ^ a3;
+        column[2] = a0 ^ a1 ^ xtime(a2) ^ (xtime(a3) ^ a3);
+        column[3] = (xtime(a0) ^ a0) ^ a1 ^ a2 ^ xtime(a3);
+    }
+}
+
+pub fn inv_mix_columns(state: &mut [u8]) {
+    for column in state.chunks_mut(4) {
+        let (a0, a1, a2, a3) = (column[0], column[1], column[2], column[3]);
+        column[0] = gmul(a0, 0x0e) ^ gmul(a1, 0x0b) ^ gmul(a2, 0x0d) ^ gmul(a3, 0x09);
+        column[1] = gmul(a0, 0x09) ^ gmul(a1, 0x0e) ^ gmul(a2, 0x0b) ^ gmul(a3, 0x0d);
+        column[2] = gmul(a0, 0x0d) ^ gmul(a1, 0x09) ^ gmul(a2, 0x0e) ^ gmul(a3, 0x0b);
+        column[3] = gmul(a0, 0x0b) ^ gmul(a1, 0x0d) ^ gmul(a2, 0x09) ^ gmul(a3, 0x0e);
+    }
+}
+
+pub fn add_round_key(state: &mut [u8], round_key: &[u8]) {
+    for (s, k) in state.iter_mut().zip(round_key) {
+        *s ^= *k;
+    }
+}
+
+fn copy_block(block: &[u8]) -> [u8; BLOCK_SIZE] {
+    let mut copy = [0u8; BLOCK_SIZE];
+    copy.copy_from_slice(block);
+    copy
+}
+
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    [SBOX[word[0] as usize],
+     SBOX[word[1] as usize],
+     SBOX[word[2] as usize],
+     SBOX[word[3] as usize]]
+}
+
+// FIPS-197 section 5.2.  Works on 32 bit words, then groups them into 16 byte
+// round keys.
+pub fn expand_key(key: &[u8]) -> Result<Vec<[u8; BLOCK_SIZE]>, CipherError> {
+    expand_key_with(key, sub_word)
+}
+
+// the key expansion with a different S-box implementation
+pub fn expand_key_with<F>(key: &[u8], sub_word: F) -> Result<Vec<[u8; BLOCK_SIZE]>, CipherError>
+    where F: Fn([u8; 4]) -> [u8; 4]
+{
+    let nk = key.len() / 4;
+    let nr = match key.len() {
+        16 => 10,
+        24 => 12,
+        32 => 14,
+        _ => return Err(CipherError::InvalidKeyLength),
+    };
+
+    let num_words = 4 * (nr + 1);
+    let mut words: Vec<[u8; 4]> = Vec::with_capacity(num_words);
+    for chunk in key.chunks(4) {
+        words.push([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    for i in nk..num_words {
+        let mut temp = words[i - 1];
+        if i % nk == 0 {
+            // RotWord, SubWord, then add the round constant
+            temp = sub_word([temp[1], temp[2], temp[3], temp[0]]);
+            temp[0] ^= RCON[i / nk - 1];
+        } else if nk > 6 && i % nk == 4 {
+            temp = sub_word(temp);
+        }
+        let prev = words[i - nk];
+        words.push([prev[0] ^ temp[0], prev[1] ^ temp[1], prev[2] ^ temp[2], prev[3] ^ temp[3]]);
+    }
+
+    Ok(words
+           .chunks(4)
+           .map(|w| {
+                    let mut round_key = [0u8; BLOCK_SIZE];
+                    for (i, word) in w.iter().enumerate() {
+                        round_key[i * 4..(i + 1) * 4].copy_from_slice(word);
+                    }
+                    round_key
+                })
+           .collect())
+}
+
+#[cfg(test)]
+fn fips197_vector(key: &str, plaintext: &str, ciphertext: &str) {
+    use hexstring::{fromhex, tohex};
+
+    // every backend, plus whatever new picks on this machine
+    let key = fromhex(key).unwrap();
+    for aes in [Aes::new_portable(&key), Aes::new_bitsliced(&key), Aes::new(&key)].iter() {
+        let aes = aes.as_ref().unwrap();
+        let mut block = fromhex(plaintext).unwrap();
+        aes.encrypt_block(&mut block);
+        assert_eq!(tohex(&block), ciphertext, "{:?}", aes.backend());
+        aes.decrypt_block(&mut block);
+        assert_eq!(tohex(&block), plaintext, "{:?}", aes.backend());
+    }
+}
+
+#[test]
+fn test_fips197_appendix_b() {
+    fips197_vector("2b7e151628aed2a6abf7158809cf4f3c",
+                   "3243f6a8885a308d313198a2e0370734",
+                   "3925841d02dc09fbdc118597196a0b32");
+}
+
+#[test]
+fn test_fips197_appendix_c() {
+    fips197_vector("000102030405060708090a0b0c0d0e0f",
+                   "00112233445566778899aabbccddeeff",
+                   "69c4e0d86a7b0430d8cdb78070b4c55a");
+    fips197_vector("000102030405060708090a0b0c0d0e0f1011121314151617",
+                   "00112233445566778899aabbccddeeff",
+                   "dda97ca4864cdfe06eaf70a0ec0d7191");
+    fips197_vector("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                   "00112233445566778899aabbccddeeff",
+                   "8ea2b7ca516745bfeafc49904b496089");
+}
+
+#[test]
+fn test_key_expansion() {
+    use hexstring::{fromhex, tohex};
+
+    // FIPS-197 appendix A.1
+    let round_keys = expand_key(&fromhex("2b7e151628aed2a6abf7158809cf4f3c").unwrap()).unwrap();
+    assert_eq!(round_keys.len(), 11);
+    assert_eq!(tohex(&round_keys[1]), "a0fafe1788542cb123a339392a6c7605");
+    assert_eq!(tohex(&round_keys[10]), "d014f9a8c9ee2589e13f0cc8b6630ca6");
+
+    assert_eq!(expand_key(&[0u8; 15]).err(), Some(CipherError::InvalidKeyLength));
+}
+
+#[test]
+fn test_trace() {
+    use hexstring::{fromhex, tohex};
+
+    // FIPS-197 appendix B "start of round" column
+    let aes = Aes::new(&fromhex("2b7e151628aed2a6abf7158809cf4f3c").unwrap()).unwrap();
+    let mut block = fromhex("3243f6a8885a308d313198a2e0370734").unwrap();
+    let mut states = Vec::new();
+    aes.encrypt_block_with_trace(&mut block, |round, state| states.push((round, tohex(state))));
+    assert_eq!(states.len(), 11);
+    assert_eq!(states[0], (1, "193de3bea0f4e22b9ac68d2ae9f84808".to_string()));
+    assert_eq!(states[1], (2, "a49c7ff2689f352b6b5bea43026a5049".to_string()));
+    assert_eq!(states[10], (11, "3925841d02dc09fbdc118597196a0b32".to_string()));
+}
+
+#[test]
+fn test_gmul() {
+    // FIPS-197 section 4.2
+    assert_eq!(gmul(0x57, 0x83), 0xc1);
+    assert_eq!(gmul(0x57, 0x13), 0xfe);
+    assert_eq!(xtime(0x57), 0xae);
+}
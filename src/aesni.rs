@@ -0,0 +1,110 @@
+// AES using the AES-NI instructions.  The key schedule is the portable one from
+// aescore.rs; the instructions just do whole rounds at a time.  Decryption uses
+// the "equivalent inverse cipher" from FIPS-197 section 5.3.5, which needs the
+// middle round keys run through InvMixColumns first.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use aescore::{inv_mix_columns, BLOCK_SIZE};
+
+// checked at runtime, so the same binary runs on CPUs without AES-NI
+#[cfg(target_arch = "x86_64")]
+pub fn available() -> bool {
+    is_x86_feature_detected!("aes") && is_x86_feature_detected!("sse2")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn available() -> bool {
+    false
+}
+
+// the decryption round keys, in the order they're used
+pub fn inverse_round_keys(round_keys: &[[u8; BLOCK_SIZE]]) -> Vec<[u8; BLOCK_SIZE]> {
+    let nr = round_keys.len() - 1;
+    let mut inverse = Vec::with_capacity(round_keys.len());
+    inverse.push(round_keys[nr]);
+    for round in (1..nr).rev() {
+        let mut key = round_keys[round];
+        inv_mix_columns(&mut key);
+        inverse.push(key);
+    }
+    inverse.push(round_keys[0]);
+    inverse
+}
+
+// Only safe to call when available() is true.  `round_keys` is either the
+// normal round keys (for encrypt_block) or the output of inverse_round_keys
+// (for decrypt_block).
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,sse2")]
+pub unsafe fn encrypt_block(round_keys: &[[u8; BLOCK_SIZE]], block: &mut [u8]) {
+    assert_eq!(block.len(), BLOCK_SIZE);
+    let nr = round_keys.len() - 1;
+    let key = |i: usize| _mm_loadu_si128(round_keys[i].as_ptr() as *const __m128i);
+
+    let mut state = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    state = _mm_xor_si128(state, key(0));
+    for round in 1..nr {
+        state = _mm_aesenc_si128(state, key(round));
+    }
+    state = _mm_aesenclast_si128(state, key(nr));
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, state);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "aes,sse2")]
+pub unsafe fn decrypt_block(inverse_round_keys: &[[u8; BLOCK_SIZE]], block: &mut [u8]) {
+    assert_eq!(block.len(), BLOCK_SIZE);
+    let nr = inverse_round_keys.len() - 1;
+    let key = |i: usize| _mm_loadu_si128(inverse_round_keys[i].as_ptr() as *const __m128i);
+
+    let mut state = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    state = _mm_xor_si128(state, key(0));
+    for round in 1..nr {
+        state = _mm_aesdec_si128(state, key(round));
+    }
+    state = _mm_aesdeclast_si128(state, key(nr));
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, state);
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub unsafe fn encrypt_block(_: &[[u8; BLOCK_SIZE]], _: &mut [u8]) {
+    unreachable!("AES-NI is only used on x86_64")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub unsafe fn decrypt_block(_: &[[u8; BLOCK_SIZE]], _: &mut [u8]) {
+    unreachable!("AES-NI is only used on x86_64")
+}
+
+#[test]
+fn test_backends_match() {
+    use aescore::{Aes, Backend};
+    use bytes::random_bytes;
+
+    if !available() {
+        return;
+    }
+    for keysize in [16, 24, 32].iter() {
+        let key = random_bytes(*keysize);
+        let hardware = Aes::new(&key).unwrap();
+        let portable = Aes::new_portable(&key).unwrap();
+        assert_eq!(hardware.backend(), Backend::AesNi);
+        assert_eq!(portable.backend(), Backend::Portable);
+
+        for _ in 0..1000 {
+            let cleartext = random_bytes(BLOCK_SIZE);
+            let mut a = cleartext.clone();
+            let mut b = cleartext.clone();
+            hardware.encrypt_block(&mut a);
+            portable.encrypt_block(&mut b);
+            assert_eq!(a, b);
+
+            hardware.decrypt_block(&mut a);
+            portable.decrypt_block(&mut b);
+            assert_eq!(a, cleartext);
+            assert_eq!(b, cleartext);
+        }
+    }
+}
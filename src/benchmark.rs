@@ -0,0 +1,157 @@
+use aes::{apply_ctr_in_place, apply_ctr_in_place_with, decrypt_cbc_in_place,
+          decrypt_cbc_in_place_with, decrypt_ecb, encrypt_cbc_in_place,
+          encrypt_cbc_in_place_with, encrypt_ecb};
+use aescore::{Aes, BLOCK_SIZE};
+use bytes::{inplace_xor, random_bytes, repeat_xor};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// Not one of the challenges - compares the in-place mode functions with the
+// way the modes used to be done: a repeat_xor and a one-block encrypt_ecb or
+// decrypt_ecb for every block, each with its own allocations and its own key
+// expansion.  The in-place functions are timed both given the key, like the
+// baseline, and given a prebuilt cipher.  It's a poor man's criterion (which
+// needs a lib crate): warm up, then time batches of calls until the time
+// budget runs out and report the median batch.  Run it with --release for
+// numbers worth quoting.
+
+static WARMUP: u64 = 50;
+static BATCH_SIZE: u32 = 100;
+
+fn budget() -> Duration {
+    Duration::from_millis(200)
+}
+
+fn bench<F: FnMut()>(f: F) -> f64 {
+    bench_for(budget(), f)
+}
+
+// median nanoseconds per call of f, timing for at least `budget`
+pub fn bench_for<F: FnMut()>(budget: Duration, mut f: F) -> f64 {
+    for _ in 0..WARMUP {
+        f();
+    }
+
+    let mut samples = Vec::new();
+    let start = Instant::now();
+    while start.elapsed() < budget || samples.len() < 5 {
+        let batch_start = Instant::now();
+        for _ in 0..BATCH_SIZE {
+            f();
+        }
+        let elapsed = batch_start.elapsed();
+        samples.push((elapsed.as_secs() as f64 * 1e9 + elapsed.subsec_nanos() as f64) /
+                     BATCH_SIZE as f64);
+    }
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    samples[samples.len() / 2]
+}
+
+// the old decrypt_cbc
+fn per_block_decrypt_cbc(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut cleartext = Vec::with_capacity(ciphertext.len());
+    let mut last_block = iv;
+    for c in ciphertext.chunks(BLOCK_SIZE) {
+        let block = inplace_xor(decrypt_ecb(c, key).unwrap(), last_block);
+        cleartext.extend_from_slice(&block);
+        last_block = c;
+    }
+    cleartext
+}
+
+// the old encrypt_cbc
+fn per_block_encrypt_cbc(cleartext: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut ciphertext = Vec::with_capacity(cleartext.len());
+    let mut last_block = iv.to_vec();
+    for c in cleartext.chunks(BLOCK_SIZE) {
+        let block = encrypt_ecb(&repeat_xor(c, &last_block), key).unwrap();
+        ciphertext.extend_from_slice(&block);
+        last_block = block;
+    }
+    ciphertext
+}
+
+// the old decrypt_ctr
+fn per_block_ctr(data: &[u8], key: &[u8], nonce: &u64) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len());
+    let mut counter = [0u8; BLOCK_SIZE];
+    for (i, d) in counter[0..8].iter_mut().enumerate() {
+        *d = (nonce >> (8 * i)) as u8;
+    }
+    for (n, block) in data.chunks(BLOCK_SIZE).enumerate() {
+        for (i, d) in counter[8..].iter_mut().enumerate() {
+            *d = ((n as u64) >> (8 * i)) as u8;
+        }
+        let encrypted_counter = encrypt_ecb(&counter, key).unwrap();
+        output.append(&mut repeat_xor(block, &encrypted_counter));
+    }
+    output
+}
+
+fn report(name: &str, baseline: f64, in_place: f64, prebuilt: f64) {
+    println!("{:<28} {:>10.0} ns {:>10.0} ns {:>6.1}x {:>10.0} ns {:>6.1}x",
+             name,
+             baseline,
+             in_place,
+             baseline / in_place,
+             prebuilt,
+             baseline / prebuilt);
+}
+
+pub fn benchmark() {
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    let cipher = Aes::new(&key).unwrap();
+    println!("{:<28} {:>13} {:>21} {:>21}", "", "per block", "in place", "prebuilt cipher");
+
+    // the shape of a challenge 17 oracle call - two blocks
+    let ciphertext = random_bytes(32);
+    let mut buffer = ciphertext.clone();
+    let baseline = bench(|| {
+                             black_box(per_block_decrypt_cbc(black_box(&ciphertext), &key, &iv));
+                         });
+    let in_place = bench(|| {
+                             buffer.copy_from_slice(&ciphertext);
+                             decrypt_cbc_in_place(black_box(&mut buffer), &key, &iv).unwrap();
+                         });
+    let prebuilt = bench(|| {
+                             buffer.copy_from_slice(&ciphertext);
+                             decrypt_cbc_in_place_with(&cipher, black_box(&mut buffer), &iv)
+                                 .unwrap();
+                         });
+    report("decrypt_cbc, 2 blocks", baseline, in_place, prebuilt);
+
+    let data = random_bytes(4096);
+    let mut buffer = data.clone();
+    let baseline = bench(|| {
+                             black_box(per_block_encrypt_cbc(black_box(&data), &key, &iv));
+                         });
+    let in_place = bench(|| {
+                             encrypt_cbc_in_place(black_box(&mut buffer), &key, &iv).unwrap();
+                         });
+    let prebuilt = bench(|| {
+                             encrypt_cbc_in_place_with(&cipher, black_box(&mut buffer), &iv)
+                                 .unwrap();
+                         });
+    report("encrypt_cbc, 4KB", baseline, in_place, prebuilt);
+
+    let baseline = bench(|| { black_box(per_block_ctr(black_box(&data), &key, &0)); });
+    let in_place = bench(|| { apply_ctr_in_place(black_box(&mut buffer), &key, &0).unwrap(); });
+    let prebuilt = bench(|| { apply_ctr_in_place_with(&cipher, black_box(&mut buffer), &0); });
+    report("ctr, 4KB", baseline, in_place, prebuilt);
+}
+
+#[test]
+fn test_baseline_matches() {
+    use aes::{decrypt_cbc, decrypt_ctr, encrypt_cbc};
+
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    let data = random_bytes(64);
+    assert_eq!(per_block_encrypt_cbc(&data, &key, &iv),
+               encrypt_cbc(&data, &key, &iv).unwrap());
+    assert_eq!(per_block_decrypt_cbc(&data, &key, &iv),
+               decrypt_cbc(&data, &key, &iv).unwrap());
+    assert_eq!(per_block_ctr(&data[..50], &key, &7),
+               decrypt_ctr(&data[..50], &key, &7).unwrap());
+}
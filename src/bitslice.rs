@@ -0,0 +1,250 @@
+// A bitsliced, constant-time AES.  The table lookups in aescore.rs index memory
+// with secret bytes, which can leak through the cache; this never does.  The
+// 16 byte state is stored as 8 bit-planes - plane i holds bit i of every byte,
+// byte j in bit j - and every step is a fixed sequence of ands, xors and shifts
+// on the planes, whatever the data.
+//
+// The S-box is computed rather than looked up: the inverse in GF(2^8) is
+// x^254, done with a fixed chain of multiplications, followed by the affine
+// transform.  That's far slower than a circuit-minimised S-box, but every step
+// can be checked against FIPS-197.
+
+use aescore::{expand_key_with, BLOCK_SIZE};
+use blockcipher::CipherError;
+
+type Planes = [u16; 8];
+
+fn slice(bytes: &[u8]) -> Planes {
+    let mut planes = [0u16; 8];
+    for (j, b) in bytes.iter().enumerate() {
+        for (i, plane) in planes.iter_mut().enumerate() {
+            *plane |= (((*b >> i) & 1) as u16) << j;
+        }
+    }
+    planes
+}
+
+fn unslice(planes: &Planes, bytes: &mut [u8]) {
+    for (j, b) in bytes.iter_mut().enumerate() {
+        *b = 0;
+        for (i, plane) in planes.iter().enumerate() {
+            *b |= (((plane >> j) & 1) as u8) << i;
+        }
+    }
+}
+
+// multiplication in GF(2^8) on every byte at once - a schoolbook polynomial
+// product, then reduction by x^8 + x^4 + x^3 + x + 1
+fn gf_mul(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for i in 0..8 {
+        for j in 0..8 {
+            product[i + j] ^= a[i] & b[j];
+        }
+    }
+    for k in (8..15).rev() {
+        product[k - 4] ^= product[k];
+        product[k - 5] ^= product[k];
+        product[k - 7] ^= product[k];
+        product[k - 8] ^= product[k];
+    }
+    let mut result = [0u16; 8];
+    result.copy_from_slice(&product[..8]);
+    result
+}
+
+// x^254, which is x^-1 for everything but 0 (which it leaves alone)
+fn gf_inverse(x: &Planes) -> Planes {
+    let mut power = gf_mul(x, x);
+    let mut result = power;
+    for _ in 0..6 {
+        power = gf_mul(&power, &power);
+        result = gf_mul(&result, &power);
+    }
+    result
+}
+
+// xor in a constant byte on every lane
+fn add_constant(planes: &mut Planes, c: u8) {
+    for (i, plane) in planes.iter_mut().enumerate() {
+        if (c >> i) & 1 == 1 {
+            *plane ^= 0xffff;
+        }
+    }
+}
+
+fn sub_bytes(state: &Planes) -> Planes {
+    let b = gf_inverse(state);
+    let mut s = [0u16; 8];
+    for i in 0..8 {
+        s[i] = b[i] ^ b[(i + 4) % 8] ^ b[(i + 5) % 8] ^ b[(i + 6) % 8] ^ b[(i + 7) % 8];
+    }
+    add_constant(&mut s, 0x63);
+    s
+}
+
+fn inv_sub_bytes(state: &Planes) -> Planes {
+    let mut b = [0u16; 8];
+    for i in 0..8 {
+        b[i] = state[(i + 2) % 8] ^ state[(i + 5) % 8] ^ state[(i + 7) % 8];
+    }
+    add_constant(&mut b, 0x05);
+    gf_inverse(&b)
+}
+
+// Byte j of the block is row j % 4, column j / 4.  Moves each byte from
+// (row, (col + shift(row)) % 4) to (row, col).
+fn permute<F: Fn(usize, usize) -> usize>(state: &mut Planes, source_col: F) {
+    for plane in state.iter_mut() {
+        let mut permuted = 0u16;
+        for j in 0..16 {
+            let (row, col) = (j % 4, j / 4);
+            let source = row + 4 * source_col(row, col);
+            permuted |= ((*plane >> source) & 1) << j;
+        }
+        *plane = permuted;
+    }
+}
+
+fn shift_rows(state: &mut Planes) {
+    permute(state, |row, col| (col + row) % 4);
+}
+
+fn inv_shift_rows(state: &mut Planes) {
+    permute(state, |row, col| (col + 4 - row) % 4);
+}
+
+// each byte replaced by the one `n` rows further down its column
+fn rotate_rows(state: &Planes, n: usize) -> Planes {
+    let mut rotated = [0u16; 8];
+    for (r, plane) in rotated.iter_mut().zip(state.iter()) {
+        for j in 0..16 {
+            let (row, col) = (j % 4, j / 4);
+            let source = (row + n) % 4 + 4 * col;
+            *r |= ((*plane >> source) & 1) << j;
+        }
+    }
+    rotated
+}
+
+// multiply by x
+fn xtime(a: &Planes) -> Planes {
+    [a[7], a[0] ^ a[7], a[1], a[2] ^ a[7], a[3] ^ a[7], a[4], a[5], a[6]]
+}
+
+fn xor(a: &Planes, b: &Planes) -> Planes {
+    let mut result = [0u16; 8];
+    for i in 0..8 {
+        result[i] = a[i] ^ b[i];
+    }
+    result
+}
+
+// 2a + 3b + c + d, where b, c and d are the next three rows down
+fn mix_columns(state: &Planes) -> Planes {
+    let r1 = rotate_rows(state, 1);
+    let r2 = rotate_rows(state, 2);
+    let r3 = rotate_rows(state, 3);
+    let doubled = xtime(&xor(state, &r1));
+    xor(&xor(&doubled, &r1), &xor(&r2, &r3))
+}
+
+// InvMixColumns is MixColumns after adding 4 times the byte two rows away
+fn inv_mix_columns(state: &Planes) -> Planes {
+    let quadrupled = xtime(&xtime(&xor(state, &rotate_rows(state, 2))));
+    mix_columns(&xor(state, &quadrupled))
+}
+
+pub fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let mut substituted = [0u8; 4];
+    unslice(&sub_bytes(&slice(&word)), &mut substituted);
+    substituted
+}
+
+// the normal key expansion, using the bitsliced S-box
+pub fn expand_key(key: &[u8]) -> Result<Vec<[u8; BLOCK_SIZE]>, CipherError> {
+    expand_key_with(key, sub_word)
+}
+
+pub fn encrypt_block(round_keys: &[[u8; BLOCK_SIZE]], block: &mut [u8]) {
+    assert_eq!(block.len(), BLOCK_SIZE);
+    let nr = round_keys.len() - 1;
+    let mut state = xor(&slice(block), &slice(&round_keys[0]));
+    for round in 1..nr {
+        state = sub_bytes(&state);
+        shift_rows(&mut state);
+        state = xor(&mix_columns(&state), &slice(&round_keys[round]));
+    }
+    state = sub_bytes(&state);
+    shift_rows(&mut state);
+    state = xor(&state, &slice(&round_keys[nr]));
+    unslice(&state, block);
+}
+
+pub fn decrypt_block(round_keys: &[[u8; BLOCK_SIZE]], block: &mut [u8]) {
+    assert_eq!(block.len(), BLOCK_SIZE);
+    let nr = round_keys.len() - 1;
+    let mut state = xor(&slice(block), &slice(&round_keys[nr]));
+    for round in (1..nr).rev() {
+        inv_shift_rows(&mut state);
+        state = inv_sub_bytes(&state);
+        state = inv_mix_columns(&xor(&state, &slice(&round_keys[round])));
+    }
+    inv_shift_rows(&mut state);
+    state = inv_sub_bytes(&state);
+    state = xor(&state, &slice(&round_keys[0]));
+    unslice(&state, block);
+}
+
+#[test]
+fn test_sbox() {
+    use aescore;
+
+    // every byte, 16 at a time
+    for start in (0..256).step_by(16) {
+        let bytes: Vec<u8> = (start..start + 16).map(|b| b as u8).collect();
+        let mut expected = bytes.clone();
+        aescore::sub_bytes(&mut expected);
+        let mut substituted = [0u8; 16];
+        unslice(&sub_bytes(&slice(&bytes)), &mut substituted);
+        assert_eq!(substituted.to_vec(), expected);
+
+        let mut inverted = [0u8; 16];
+        unslice(&inv_sub_bytes(&slice(&substituted)), &mut inverted);
+        assert_eq!(inverted.to_vec(), bytes);
+    }
+}
+
+#[test]
+fn test_bitsliced_aes() {
+    use aescore::{self, Aes, Backend};
+    use bytes::random_bytes;
+    use hexstring::{fromhex, tohex};
+
+    // FIPS-197 appendix C
+    let aes = Aes::new_bitsliced(&fromhex("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap();
+    assert_eq!(aes.backend(), Backend::Bitsliced);
+    let mut block = fromhex("00112233445566778899aabbccddeeff").unwrap();
+    aes.encrypt_block(&mut block);
+    assert_eq!(tohex(&block), "69c4e0d86a7b0430d8cdb78070b4c55a");
+    aes.decrypt_block(&mut block);
+    assert_eq!(tohex(&block), "00112233445566778899aabbccddeeff");
+
+    for keysize in [16, 24, 32].iter() {
+        let key = random_bytes(*keysize);
+        assert_eq!(expand_key(&key).unwrap(), aescore::expand_key(&key).unwrap());
+
+        let bitsliced = Aes::new_bitsliced(&key).unwrap();
+        let portable = Aes::new_portable(&key).unwrap();
+        for _ in 0..20 {
+            let cleartext = random_bytes(BLOCK_SIZE);
+            let mut a = cleartext.clone();
+            let mut b = cleartext.clone();
+            bitsliced.encrypt_block(&mut a);
+            portable.encrypt_block(&mut b);
+            assert_eq!(a, b);
+            bitsliced.decrypt_block(&mut a);
+            assert_eq!(a, cleartext);
+        }
+    }
+}
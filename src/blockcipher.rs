@@ -0,0 +1,112 @@
+use bytes::PaddingError;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CipherError {
+    InvalidKeyLength,
+    InvalidLength,
+    CounterOverflow,
+    Padding(PaddingError),
+    #[allow(dead_code)]
+    AuthenticationFailed,
+}
+
+impl From<PaddingError> for CipherError {
+    fn from(err: PaddingError) -> CipherError {
+        CipherError::Padding(err)
+    }
+}
+
+// No cipher here has bigger blocks than AES, so code that wants a block buffer
+// without allocating can use an array this size.
+pub const MAX_BLOCK_SIZE: usize = 16;
+
+// Anything that can encrypt and decrypt a single fixed-size block in place.
+// The modes in aes.rs are written against this, so they work with ciphers
+// other than AES.
+pub trait BlockCipher {
+    fn block_size(&self) -> usize;
+    fn encrypt_block(&self, block: &mut [u8]);
+    fn decrypt_block(&self, block: &mut [u8]);
+}
+
+// A deliberately weak cipher with a configurable (tiny) block size, for
+// running the block cipher attacks where every block can be read at a glance.
+// Each round adds in the key, rotates each byte and then adds in its
+// neighbour (if it has one), which is enough to be a permutation and little
+// else.
+#[allow(dead_code)]
+pub struct ToyCipher {
+    key: Vec<u8>,
+    block_size: usize,
+}
+
+#[allow(dead_code)]
+const TOY_ROUNDS: usize = 4;
+
+impl ToyCipher {
+    #[allow(dead_code)]
+    pub fn new(key: &[u8], block_size: usize) -> Result<ToyCipher, CipherError> {
+        if key.is_empty() {
+            return Err(CipherError::InvalidKeyLength);
+        }
+        if block_size == 0 {
+            return Err(CipherError::InvalidLength);
+        }
+        Ok(ToyCipher {
+               key: key.to_vec(),
+               block_size: block_size,
+           })
+    }
+}
+
+impl BlockCipher for ToyCipher {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), self.block_size);
+        let n = block.len();
+        for round in 0..TOY_ROUNDS {
+            for i in 0..n {
+                let k = self.key[(i + round) % self.key.len()];
+                block[i] = (block[i] ^ k).rotate_left(3);
+                if n > 1 {
+                    block[i] = block[i].wrapping_add(block[(i + n - 1) % n]);
+                }
+            }
+        }
+    }
+
+    // Undo each byte in the reverse order - that way the neighbour that was
+    // added in always has the same value it had during encryption.
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), self.block_size);
+        let n = block.len();
+        for round in (0..TOY_ROUNDS).rev() {
+            for i in (0..n).rev() {
+                let k = self.key[(i + round) % self.key.len()];
+                if n > 1 {
+                    block[i] = block[i].wrapping_sub(block[(i + n - 1) % n]);
+                }
+                block[i] = block[i].rotate_right(3) ^ k;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_toy_cipher() {
+    use bytes::random_bytes;
+
+    for block_size in 1..9 {
+        let cipher = ToyCipher::new(b"toy", block_size).unwrap();
+        for _ in 0..100 {
+            let cleartext = random_bytes(block_size);
+            let mut block = cleartext.clone();
+            cipher.encrypt_block(&mut block);
+            cipher.decrypt_block(&mut block);
+            assert_eq!(block, cleartext);
+        }
+    }
+}
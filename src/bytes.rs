@@ -1,5 +1,6 @@
 use rand;
 use rand::Rng;
+use scoring::{ChiSquared, Scorer};
 use std::cmp::Ordering;
 
 pub fn xor1(bytes: &[u8], byte: u8) -> Vec<u8> {
@@ -17,6 +18,7 @@ pub fn random_bytes(n: usize) -> Vec<u8> {
 }
 
 // Give a rating of how much this text looks like english, divided by the length
+#[allow(dead_code)]
 pub fn englishness(cleartext: &[u8]) -> f32 {
     let num_english_chars: u32 = cleartext
         .iter()
@@ -67,14 +69,79 @@ pub fn hamming_distance(a: &[u8], b: &[u8]) -> u32 {
         .sum()
 }
 
+// splits `data` into `keysize` columns, byte i going in column i % keysize
+pub fn transpose(data: &[u8], keysize: usize) -> Vec<Vec<u8>> {
+    // create our vec of vecs
+    let mut transposed = Vec::new();
+    for _ in 0..keysize {
+        transposed.push(Vec::new());
+    }
+
+    // go through each chunk and add the byte to the appropriate vec in
+    // transposed
+    for c in data.chunks(keysize) {
+        for (i, b) in c.iter().enumerate() {
+            transposed[i].push(*b);
+        }
+    }
+    transposed
+}
+
+// rated by English letter frequencies - see scoring::ChiSquared
 pub fn most_english_xor(ciphertext: &[u8]) -> Option<(u8, f32, Vec<u8>)> {
-    (0..0xff)
+    most_likely_xor(ciphertext, &ChiSquared::english())
+}
+
+// the single byte xor key whose cleartext `scorer` rates highest, with its
+// score and the cleartext
+pub fn most_likely_xor<S: Scorer + ?Sized>(ciphertext: &[u8],
+                                           scorer: &S)
+                                           -> Option<(u8, f32, Vec<u8>)> {
+    ranked_xors(ciphertext, scorer, 1).pop()
+}
+
+// Every one of the 256 single byte xor keys, rated by `scorer`, and the `n`
+// best returned best first (with their scores and cleartexts) so that a
+// solver can fall back on the runners up when the best doesn't fit.
+pub fn ranked_xors<S: Scorer + ?Sized>(ciphertext: &[u8],
+                                       scorer: &S,
+                                       n: usize)
+                                       -> Vec<(u8, f32, Vec<u8>)> {
+    let mut candidates = (0..256)
         .map(|k| {
-                 let cleartext = xor1(&ciphertext, k);
-                 let rating = englishness(&cleartext);
-                 (k, rating, cleartext)
+                 let cleartext = xor1(&ciphertext, k as u8);
+                 let rating = scorer.score(&cleartext);
+                 (k as u8, rating, cleartext)
              })
-        .max_by(|x, y| x.1.partial_cmp(&y.1).unwrap_or(Ordering::Less))
+        .collect::<Vec<_>>();
+    candidates.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap_or(Ordering::Equal));
+    candidates.truncate(n);
+    candidates
+}
+
+// Control characters other than whitespace, which text doesn't have - a key
+// that leaves any in the cleartext is wrong however well it rates.
+pub fn has_control_chars(text: &[u8]) -> bool {
+    text.iter()
+        .any(|b| (*b < 0x20 && !b"\t\n\r".contains(b)) || *b == 0x7f)
+}
+
+// The best of the `n` most likely keys whose cleartext has no control
+// characters, or the most likely key if none of them manage it.
+pub fn most_likely_text_xor<S: Scorer + ?Sized>(ciphertext: &[u8],
+                                                scorer: &S,
+                                                n: usize)
+                                                -> Option<(u8, f32, Vec<u8>)> {
+    let mut candidates = ranked_xors(ciphertext, scorer, n);
+    let position = candidates
+        .iter()
+        .position(|c| !has_control_chars(&c.2))
+        .unwrap_or(0);
+    if candidates.is_empty() {
+        None
+    } else {
+        Some(candidates.swap_remove(position))
+    }
 }
 
 // pkcs7 pad `bytes` to an even `bocksize`
@@ -85,31 +152,46 @@ pub fn pad(mut bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
     bytes
 }
 
-pub fn unpad(mut bytes: Vec<u8>) -> Vec<u8> {
-    let num_padding_bytes = bytes.pop();
-    match num_padding_bytes {
-        Some(n) => {
-            // already removed one above...
-            let new_len = bytes.len() + 1 - n as usize;
-            bytes.resize(new_len, 0);
-            bytes
-        }
-        None => bytes,
-    }
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PaddingError {
+    Empty,
+    NotBlockAligned,
+    BadPaddingLength(u8),
+    BadPaddingByte,
 }
 
-pub fn valid_padding(bytes: &[u8], blocksize: usize) -> bool {
+// check pkcs7 padding, returning the number of padding bytes
+pub fn check_padding(bytes: &[u8], blocksize: usize) -> Result<usize, PaddingError> {
     let bytes_len = bytes.len();
+    if bytes_len == 0 {
+        return Err(PaddingError::Empty);
+    }
     if bytes_len % blocksize != 0 {
-        return false;
+        return Err(PaddingError::NotBlockAligned);
     }
     let pad_value = bytes[bytes_len - 1];
-    if pad_value > blocksize as u8 {
-        return false;
+    if pad_value == 0 || pad_value as usize > blocksize {
+        return Err(PaddingError::BadPaddingLength(pad_value));
     }
-    ((bytes_len - (pad_value as usize))..(bytes_len - 1))
-        .map(|i| bytes[i])
-        .all(|v| v == pad_value)
+    if bytes[(bytes_len - pad_value as usize)..]
+           .iter()
+           .all(|v| *v == pad_value) {
+        Ok(pad_value as usize)
+    } else {
+        Err(PaddingError::BadPaddingByte)
+    }
+}
+
+pub fn valid_padding(bytes: &[u8], blocksize: usize) -> bool {
+    check_padding(bytes, blocksize).is_ok()
+}
+
+// remove pkcs7 padding, failing rather than guessing if it's malformed
+pub fn strip_padding(mut bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError> {
+    let num_padding_bytes = try!(check_padding(&bytes, blocksize));
+    let new_len = bytes.len() - num_padding_bytes;
+    bytes.truncate(new_len);
+    Ok(bytes)
 }
 
 // from 0-1, what percentage of blocks in ciphertext are unique
@@ -173,7 +255,7 @@ fn test_padding() {
                pad(vec![1, 2, 3, 4, 5, 6, 7, 8], 8).as_slice());
 
     let unpadded = vec![1, 2, 3, 4, 5];
-    assert_eq!(unpadded, unpad(pad(unpadded.clone(), 8)));
+    assert_eq!(Ok(unpadded.clone()), strip_padding(pad(unpadded, 8), 8));
 }
 
 #[test]
@@ -194,3 +276,52 @@ fn test_valid_padding() {
         }
     }
 }
+
+#[test]
+fn test_strip_padding() {
+    assert_eq!(strip_padding(pad(vec![1, 2, 3], 4), 4), Ok(vec![1, 2, 3]));
+    assert_eq!(strip_padding(pad(vec![1, 2, 3, 4], 4), 4), Ok(vec![1, 2, 3, 4]));
+    assert_eq!(strip_padding(vec![], 4), Err(PaddingError::Empty));
+    assert_eq!(strip_padding(vec![1, 2, 3], 4), Err(PaddingError::NotBlockAligned));
+    assert_eq!(strip_padding(vec![1, 2, 3, 0], 4), Err(PaddingError::BadPaddingLength(0)));
+    assert_eq!(strip_padding(vec![1, 2, 3, 5], 4), Err(PaddingError::BadPaddingLength(5)));
+    assert_eq!(strip_padding(vec![1, 2, 3, 2], 4), Err(PaddingError::BadPaddingByte));
+
+    // these used to panic or pass
+    assert!(!valid_padding(b"", 16));
+    assert!(!valid_padding(b"ICE ICE BABY\x04\x04\x04\x00", 16));
+}
+
+#[test]
+fn test_ranked_xors() {
+    let ciphertext = xor1(b"Now that the party is jumping", 0xff);
+    let ranked = ranked_xors(&ciphertext, &ChiSquared::english(), 5);
+    assert_eq!(ranked.len(), 5);
+    assert_eq!(ranked[0].0, 0xff);
+    assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+    assert_eq!(most_english_xor(&ciphertext).unwrap().0, 0xff);
+    assert_eq!(ranked_xors(&ciphertext, &ChiSquared::english(), 1000).len(), 256);
+
+    assert!(has_control_chars(b"\x00abc"));
+    assert!(!has_control_chars(b"line one\r\n\tline two"));
+}
+
+#[test]
+fn test_most_likely_text_xor() {
+    use scoring::Scorer;
+
+    // rates a cleartext by how many 'a's it has
+    struct CountA;
+    impl Scorer for CountA {
+        fn score(&self, text: &[u8]) -> f32 {
+            text.iter().filter(|b| **b == b'a').count() as f32
+        }
+    }
+
+    // the best key leaves a control character...
+    let ciphertext = b"aaa\x01!!";
+    assert_eq!(most_likely_xor(ciphertext, &CountA).unwrap().0, 0);
+    // ...so the runner up is taken
+    assert_eq!(most_likely_text_xor(ciphertext, &CountA, 4).unwrap(),
+               (0x40, 2.0, b"!!!Aaa".to_vec()));
+}
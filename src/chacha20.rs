@@ -0,0 +1,265 @@
+use blockcipher::CipherError;
+use poly1305::{poly1305, TAG_SIZE};
+
+// ChaCha20 and the ChaCha20-Poly1305 AEAD, as in RFC 8439.  ChaCha is a
+// rearranged Salsa20 - the same add-rotate-xor rounds in a different order -
+// with a 32 bit block counter and a 96 bit nonce.
+
+pub const BLOCK_SIZE: usize = 64;
+pub const KEY_SIZE: usize = 32;
+pub const NONCE_SIZE: usize = 12;
+
+fn le_word(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0u32, |acc, b| (acc << 8) | *b as u32)
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+// one 64 byte block of keystream
+pub fn chacha20_block(key: &[u8], counter: u32, nonce: &[u8]) -> [u8; BLOCK_SIZE] {
+    assert_eq!(key.len(), KEY_SIZE);
+    assert_eq!(nonce.len(), NONCE_SIZE);
+
+    let mut input = [0u32; 16];
+    for (i, word) in b"expand 32-byte k".chunks(4).chain(key.chunks(4)).enumerate() {
+        input[i] = le_word(word);
+    }
+    input[12] = counter;
+    for (i, word) in nonce.chunks(4).enumerate() {
+        input[13 + i] = le_word(word);
+    }
+
+    let mut x = input;
+    for _ in 0..10 {
+        // columns
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 1, 5, 9, 13);
+        quarter_round(&mut x, 2, 6, 10, 14);
+        quarter_round(&mut x, 3, 7, 11, 15);
+        // diagonals
+        quarter_round(&mut x, 0, 5, 10, 15);
+        quarter_round(&mut x, 1, 6, 11, 12);
+        quarter_round(&mut x, 2, 7, 8, 13);
+        quarter_round(&mut x, 3, 4, 9, 14);
+    }
+
+    let mut output = [0u8; BLOCK_SIZE];
+    for (i, chunk) in output.chunks_mut(4).enumerate() {
+        let word = x[i].wrapping_add(input[i]);
+        for (j, b) in chunk.iter_mut().enumerate() {
+            *b = (word >> (8 * j)) as u8;
+        }
+    }
+    output
+}
+
+pub struct ChaCha20 {
+    key: Vec<u8>,
+    nonce: Vec<u8>,
+    // a u64 so that running off the end of the 32 bit counter shows
+    counter: u64,
+    keystream: [u8; BLOCK_SIZE],
+    keystream_pos: usize,
+}
+
+impl ChaCha20 {
+    pub fn new(key: &[u8], nonce: &[u8], counter: u32) -> Result<ChaCha20, CipherError> {
+        if key.len() != KEY_SIZE {
+            return Err(CipherError::InvalidKeyLength);
+        }
+        if nonce.len() != NONCE_SIZE {
+            return Err(CipherError::InvalidLength);
+        }
+        Ok(ChaCha20 {
+               key: key.to_vec(),
+               nonce: nonce.to_vec(),
+               counter: counter as u64,
+               keystream: [0u8; BLOCK_SIZE],
+               keystream_pos: BLOCK_SIZE,
+           })
+    }
+
+    // Fails rather than let the 32 bit counter wrap and reuse keystream.
+    // Nothing is changed if it fails.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), CipherError> {
+        let buffered = BLOCK_SIZE - self.keystream_pos;
+        let blocks_needed = (data.len().saturating_sub(buffered) + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        if self.counter + blocks_needed as u64 > 1 << 32 {
+            return Err(CipherError::CounterOverflow);
+        }
+
+        for b in data.iter_mut() {
+            if self.keystream_pos == BLOCK_SIZE {
+                self.keystream = chacha20_block(&self.key, self.counter as u32, &self.nonce);
+                self.counter += 1;
+                self.keystream_pos = 0;
+            }
+            *b ^= self.keystream[self.keystream_pos];
+            self.keystream_pos += 1;
+        }
+        Ok(())
+    }
+}
+
+// encrypts or decrypts, starting from block `counter`
+#[allow(dead_code)]
+pub fn chacha20(data: &[u8],
+                key: &[u8],
+                nonce: &[u8],
+                counter: u32)
+                -> Result<Vec<u8>, CipherError> {
+    let mut output = data.to_vec();
+    try!(try!(ChaCha20::new(key, nonce, counter)).apply_keystream(&mut output));
+    Ok(output)
+}
+
+// The Poly1305 key is the start of block 0; the data is the AAD and the
+// ciphertext, each zero padded to 16 bytes, then both lengths as 64 bit
+// little endian numbers.
+#[allow(dead_code)]
+fn compute_tag(key: &[u8], nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+    let mut poly_key = [0u8; 32];
+    poly_key.copy_from_slice(&chacha20_block(key, 0, nonce)[..32]);
+
+    let mut mac_data = Vec::new();
+    for data in [aad, ciphertext].iter() {
+        mac_data.extend_from_slice(data);
+        let padding = (16 - data.len() % 16) % 16;
+        mac_data.extend(vec![0u8; padding]);
+    }
+    for len in [aad.len(), ciphertext.len()].iter() {
+        for i in 0..8 {
+            mac_data.push((*len as u64 >> (8 * i)) as u8);
+        }
+    }
+    poly1305(&mac_data, &poly_key)
+}
+
+// returns (ciphertext, tag)
+#[allow(dead_code)]
+pub fn chacha20_poly1305_encrypt(key: &[u8],
+                                 nonce: &[u8],
+                                 aad: &[u8],
+                                 cleartext: &[u8])
+                                 -> Result<(Vec<u8>, [u8; TAG_SIZE]), CipherError> {
+    let ciphertext = try!(chacha20(cleartext, key, nonce, 1));
+    let tag = compute_tag(key, nonce, aad, &ciphertext);
+    Ok((ciphertext, tag))
+}
+
+// checks the tag before decrypting anything
+#[allow(dead_code)]
+pub fn chacha20_poly1305_decrypt(key: &[u8],
+                                 nonce: &[u8],
+                                 aad: &[u8],
+                                 ciphertext: &[u8],
+                                 tag: &[u8])
+                                 -> Result<Vec<u8>, CipherError> {
+    if tag.len() != TAG_SIZE {
+        return Err(CipherError::InvalidLength);
+    }
+    // checks the key and nonce before compute_tag can trip over them
+    let mut cipher = try!(ChaCha20::new(key, nonce, 1));
+    let expected_tag = compute_tag(key, nonce, aad, ciphertext);
+
+    // compare every byte, rather than stopping at the first difference
+    let difference = expected_tag
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return Err(CipherError::AuthenticationFailed);
+    }
+
+    let mut cleartext = ciphertext.to_vec();
+    try!(cipher.apply_keystream(&mut cleartext));
+    Ok(cleartext)
+}
+
+#[cfg(test)]
+static SUNSCREEN: &'static [u8] = b"Ladies and Gentlemen of the class of '99: If I could offer \
+                                    you only one tip for the future, sunscreen would be it.";
+
+#[test]
+fn test_chacha20() {
+    use bytes::random_bytes;
+    use hexstring::{fromhex, tohex};
+
+    let key: Vec<u8> = (0..32).collect();
+
+    // RFC 8439 2.3.2
+    let block = chacha20_block(&key, 1, &fromhex("000000090000004a00000000").unwrap());
+    assert_eq!(tohex(&block),
+               "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e\
+                d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e");
+
+    // RFC 8439 2.4.2
+    let nonce = fromhex("000000000000004a00000000").unwrap();
+    let ciphertext = chacha20(SUNSCREEN, &key, &nonce, 1).unwrap();
+    assert_eq!(tohex(&ciphertext),
+               "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b\
+                f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8\
+                07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736\
+                5af90bbf74a35be6b40b8eedf2785e42874d");
+    assert_eq!(chacha20(&ciphertext, &key, &nonce, 1).unwrap(), SUNSCREEN);
+
+    // pieces of any size continue the same keystream
+    let cleartext = random_bytes(300);
+    let mut cipher = ChaCha20::new(&key, &nonce, 1).unwrap();
+    let mut pieces = cleartext.clone();
+    for chunk in pieces.chunks_mut(23) {
+        cipher.apply_keystream(chunk).unwrap();
+    }
+    assert_eq!(pieces, chacha20(&cleartext, &key, &nonce, 1).unwrap());
+
+    // the last block is fine, going past it isn't
+    let mut cipher = ChaCha20::new(&key, &nonce, 0xffffffff).unwrap();
+    assert!(cipher.apply_keystream(&mut [0u8; 64]).is_ok());
+    assert!(cipher.apply_keystream(&mut [0u8; 1]).is_err());
+    assert_eq!(chacha20(&[0u8; 65], &key, &nonce, 0xffffffff).err(),
+               Some(CipherError::CounterOverflow));
+    assert!(ChaCha20::new(&key[..16], &nonce, 0).is_err());
+}
+
+#[test]
+fn test_chacha20_poly1305() {
+    use hexstring::{fromhex, tohex};
+
+    // RFC 8439 2.8.2
+    let key: Vec<u8> = (0x80..0xa0).collect();
+    let nonce = fromhex("070000004041424344454647").unwrap();
+    let aad = fromhex("50515253c0c1c2c3c4c5c6c7").unwrap();
+    let (ciphertext, tag) = chacha20_poly1305_encrypt(&key, &nonce, &aad, SUNSCREEN).unwrap();
+    assert_eq!(tohex(&ciphertext),
+               "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+                3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+                92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+                3ff4def08e4b7a9de576d26586cec64b6116");
+    assert_eq!(tohex(&tag), "1ae10b594f09e26a7e902ecbd0600691");
+    assert_eq!(chacha20_poly1305_decrypt(&key, &nonce, &aad, &ciphertext, &tag).unwrap(),
+               SUNSCREEN);
+
+    // any change to the AAD, ciphertext or tag is caught
+    let mut bad_aad = aad.clone();
+    bad_aad[0] ^= 1;
+    let mut bad_ciphertext = ciphertext.clone();
+    bad_ciphertext[100] ^= 1;
+    let mut bad_tag = tag;
+    bad_tag[15] ^= 1;
+    for &(a, c, t) in [(&bad_aad[..], &ciphertext[..], &tag[..]),
+                       (&aad[..], &bad_ciphertext[..], &tag[..]),
+                       (&aad[..], &ciphertext[..], &bad_tag[..])]
+                .iter() {
+        assert_eq!(chacha20_poly1305_decrypt(&key, &nonce, a, c, t).err(),
+                   Some(CipherError::AuthenticationFailed));
+    }
+}
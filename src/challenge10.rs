@@ -1,9 +1,8 @@
 use base64::{decode_config, MIME};
-use bytes::unpad;
-use aes::decrypt_cbc;
+use aes::decrypt_cbc_padded;
 
 pub fn challenge10() {
     let ciphertext = decode_config(include_str!("challenge-10.dat"), MIME).unwrap();
-    let cleartext = unpad(decrypt_cbc(&ciphertext, b"YELLOW SUBMARINE", &vec![0;16]).unwrap());
+    let cleartext = decrypt_cbc_padded(&ciphertext, b"YELLOW SUBMARINE", &vec![0;16]).unwrap();
     println!("{}", String::from_utf8_lossy(&cleartext));
 }
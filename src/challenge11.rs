@@ -1,7 +1,7 @@
 use rand;
 use rand::Rng;
 use aes::{encrypt_cbc, encrypt_ecb};
-use crypto::symmetriccipher;
+use aes::CipherError;
 use bytes::{pad, percent_unique_blocks, rand_u8};
 
 fn random_aes_key() -> [u8; 16] {
@@ -15,7 +15,7 @@ struct OracleResults {
     is_ecb: bool,
 }
 
-fn oracle(data: &[u8]) -> Result<OracleResults, symmetriccipher::SymmetricCipherError> {
+fn oracle(data: &[u8]) -> Result<OracleResults, CipherError> {
     let key = random_aes_key();
     let iv = random_aes_key();
 
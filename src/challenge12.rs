@@ -1,31 +1,37 @@
-use aes::encrypt_ecb;
-use crypto::symmetriccipher;
+use aes::{encrypt_ecb_with, CipherError};
+use aescore::Aes;
+use blockcipher::BlockCipher;
 use bytes::pad;
+use des::{Des, TripleDes};
 use base64::decode;
 use std::ops::Range;
 
-fn oracle(data: &[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
+fn oracle(data: &[u8]) -> Result<Vec<u8>, CipherError> {
+    oracle_with(&try!(Aes::new(&[1u8; 16])), data)
+}
+
+// the same oracle, but with any block cipher doing the encrypting
+fn oracle_with<C: BlockCipher>(cipher: &C, data: &[u8]) -> Result<Vec<u8>, CipherError> {
     let suffix = decode("Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkg\
          aGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBq\
          dXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUg\
          YnkK")
             .unwrap();
-    let key = [1u8; 16];
 
     let mut cleartext = data.iter()
         .chain(suffix.iter())
         .cloned()
         .collect::<Vec<u8>>();
-    cleartext = pad(cleartext, key.len());
+    cleartext = pad(cleartext, cipher.block_size());
 
-    encrypt_ecb(&cleartext, &key)
+    encrypt_ecb_with(cipher, &cleartext)
 }
 
 // given an encryption function, keep feeding it data until it expands it's
 // ciphertext length, allowing us to determine the blocksize
-pub fn find_blocksize(oracle_fn: fn(&[u8])
-                                    -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>)
-                      -> usize {
+pub fn find_blocksize<F>(oracle_fn: F) -> usize
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
+{
 
     let mut initial_size = oracle_fn(b"").unwrap().len();
     let mut i = 0;
@@ -56,7 +62,7 @@ pub fn find_blocksize(oracle_fn: fn(&[u8])
 }
 
 pub fn is_ecb<F>(oracle_fn: F, blocksize: usize) -> bool
-    where F: Fn(&[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
 {
 
     let ciphertext = oracle_fn(&vec![0u8; blocksize*2]).unwrap();
@@ -64,13 +70,14 @@ pub fn is_ecb<F>(oracle_fn: F, blocksize: usize) -> bool
 }
 
 pub fn length_of_suffix<F>(oracle_fn: F, blocksize: usize) -> usize
-    where F: Fn(&[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
 {
 
     let mut last_cipher_len: Option<usize> = None;
 
-    // keep adding to cleartext size until we overflow a block
-    for i in 0..blocksize {
+    // keep adding to cleartext size until we overflow a block.  If the suffix
+    // is already block aligned it takes a whole block to overflow.
+    for i in 0..(blocksize + 1) {
         let cur_cipher_len = oracle_fn(&vec![1u8;i]).unwrap().len();
         let overflowed = match last_cipher_len {
             None => {
@@ -81,14 +88,10 @@ pub fn length_of_suffix<F>(oracle_fn: F, blocksize: usize) -> usize
             _ => true,
         };
 
-        // need to add i to make it overflow, so the suffix's length (mod
-        // blocksize) is blocksize - (i - 1)
+        // need to add i to make it overflow, so suffix + i exactly filled
+        // the blocks of last_cipher_len (which was suffix + padding)
         if overflowed {
-            let suffix_mod_blocksize = blocksize - (i - 1);
-
-            // last_cipher_len was suffix + padding, so round down to nearest
-            // blocksize and then add the extra suffix length
-            let suffix_len = last_cipher_len.unwrap() - blocksize + suffix_mod_blocksize;
+            let suffix_len = last_cipher_len.unwrap() - i;
             return suffix_len;
         }
     }
@@ -100,8 +103,8 @@ pub fn crack_ecb_with_known_blocksize_and_suffix<F>
     (oracle_fn: F,
      blocksize: usize,
      suffix_len: usize)
-     -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>
-    where F: Fn(&[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>
+     -> Result<Vec<u8>, CipherError>
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
 {
 
     let mut decrypted_suffix = Vec::with_capacity(suffix_len);
@@ -151,13 +154,64 @@ pub fn crack_ecb_with_known_blocksize_and_suffix<F>
     }
 }
 
+// the whole attack, against any oracle
+fn crack<F>(oracle_fn: F) -> (usize, bool, Vec<u8>)
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
+{
+    let blocksize = find_blocksize(&oracle_fn);
+    let using_ecb = is_ecb(&oracle_fn, blocksize);
+    let suffix_length = length_of_suffix(&oracle_fn, blocksize);
+    let suffix = crack_ecb_with_known_blocksize_and_suffix(&oracle_fn, blocksize, suffix_length);
+    (blocksize, using_ecb, suffix.unwrap())
+}
+
 pub fn challenge12() {
-    let blocksize = find_blocksize(oracle);
-    let using_ecb = is_ecb(oracle, blocksize);
-    let suffix_length = length_of_suffix(oracle, blocksize);
-    let suffix = crack_ecb_with_known_blocksize_and_suffix(oracle, blocksize, suffix_length);
+    let (blocksize, using_ecb, suffix) = crack(oracle);
     print!("blocksize = {} ", blocksize);
     println!("is ecb {}", using_ecb);
-    println!("suffix len {}", suffix_length);
-    println!("suffix {}", String::from_utf8_lossy(&suffix.unwrap()));
+    println!("suffix len {}", suffix.len());
+    println!("suffix {}", String::from_utf8_lossy(&suffix));
+
+    // and again with 8 byte blocks
+    let des = Des::new(b"8bytekey").unwrap();
+    let triple_des = TripleDes::new(b"three keys, 24 bytes!!!!").unwrap();
+    let (blocksize, using_ecb, suffix) = crack(|data: &[u8]| oracle_with(&des, data));
+    println!("DES: blocksize = {} is ecb {} suffix len {}", blocksize, using_ecb, suffix.len());
+    let (blocksize, using_ecb, suffix) = crack(|data: &[u8]| oracle_with(&triple_des, data));
+    println!("3DES: blocksize = {} is ecb {} suffix len {}", blocksize, using_ecb, suffix.len());
+}
+
+#[test]
+fn test_small_blocks() {
+    use blockcipher::ToyCipher;
+
+    // none of the attack depends on AES's 16 byte blocks
+    for blocksize in [3, 4, 8].iter() {
+        let cipher = ToyCipher::new(b"secret", *blocksize).unwrap();
+        let toy_oracle = |data: &[u8]| oracle_with(&cipher, data);
+
+        assert_eq!(find_blocksize(&toy_oracle), *blocksize);
+        assert!(is_ecb(&toy_oracle, *blocksize));
+        let suffix_length = length_of_suffix(&toy_oracle, *blocksize);
+        assert_eq!(suffix_length, 138);
+        let suffix = crack_ecb_with_known_blocksize_and_suffix(&toy_oracle,
+                                                               *blocksize,
+                                                               suffix_length);
+        assert!(String::from_utf8_lossy(&suffix.unwrap()).starts_with("Rollin' in my 5.0"));
+    }
+}
+
+#[test]
+fn test_des() {
+    let des = Des::new(b"8bytekey").unwrap();
+    let (blocksize, using_ecb, suffix) = crack(|data: &[u8]| oracle_with(&des, data));
+    assert_eq!(blocksize, 8);
+    assert!(using_ecb);
+    assert!(String::from_utf8_lossy(&suffix).starts_with("Rollin' in my 5.0"));
+
+    let triple_des = TripleDes::new(&[7u8; 16]).unwrap();
+    let triple_des_oracle = |data: &[u8]| oracle_with(&triple_des, data);
+    assert_eq!(find_blocksize(&triple_des_oracle), 8);
+    assert!(is_ecb(&triple_des_oracle, 8));
+    assert_eq!(length_of_suffix(&triple_des_oracle, 8), 138);
 }
@@ -1,23 +1,19 @@
 use std::collections::HashMap;
 
-use crypto::symmetriccipher;
-use bytes::{pad, unpad};
-use aes::{encrypt_ecb, decrypt_ecb};
+use aes::CipherError;
+use aes::{encrypt_ecb_padded, decrypt_ecb_padded};
 
 fn encrypt(data: &[u8]) -> Vec<u8> {
     let key = [1u8; 16];
-
-    let mut cleartext = data.iter().cloned().collect::<Vec<u8>>();
-    cleartext = pad(cleartext, key.len());
-    encrypt_ecb(&cleartext, &key).unwrap()
+    encrypt_ecb_padded(data, &key).unwrap()
 }
 
 fn decrypt(ciphertext: &[u8])
-           -> Result<HashMap<String, String>, symmetriccipher::SymmetricCipherError> {
+           -> Result<HashMap<String, String>, CipherError> {
 
     let key = [1u8; 16];
-    let cleartext = try!(decrypt_ecb(ciphertext, &key));
-    Ok(parse_cookie(&String::from_utf8_lossy(&unpad(cleartext))))
+    let cleartext = try!(decrypt_ecb_padded(ciphertext, &key));
+    Ok(parse_cookie(&String::from_utf8_lossy(&cleartext)))
 }
 
 fn oracle(email: &str) -> Vec<u8> {
@@ -1,11 +1,18 @@
-use aes::encrypt_ecb;
-use crypto::symmetriccipher;
+use aes::{encrypt_ecb_with, CipherError};
+use aescore::Aes;
+use blockcipher::BlockCipher;
 use bytes::pad;
+use des::{Des, TripleDes};
 use base64::decode;
 use hexstring::fromhex;
 use challenge12;
 
-fn oracle(data: &[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
+fn oracle(data: &[u8]) -> Result<Vec<u8>, CipherError> {
+    oracle_with(&try!(Aes::new(&[1u8; 16])), data)
+}
+
+// the same oracle, but with any block cipher doing the encrypting
+fn oracle_with<C: BlockCipher>(cipher: &C, data: &[u8]) -> Result<Vec<u8>, CipherError> {
     let suffix = decode("Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkg\
          aGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBq\
          dXN0IHRvIHNheSBoaQpEaWQgeW91IHN0b3A/IE5vLCBJIGp1c3QgZHJvdmUg\
@@ -14,7 +21,6 @@ fn oracle(data: &[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>
     let prefix = fromhex("707e8df2087691cf209d9660c6ae1f9982305b848c0e3597365facb808be27e0\
                           16f98500e6")
             .unwrap();
-    let key = [1u8; 16];
 
     let mut cleartext = prefix
         .iter()
@@ -22,14 +28,14 @@ fn oracle(data: &[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>
         .chain(suffix.iter())
         .cloned()
         .collect::<Vec<u8>>();
-    cleartext = pad(cleartext, key.len());
+    cleartext = pad(cleartext, cipher.block_size());
 
-    encrypt_ecb(&cleartext, &key)
+    encrypt_ecb_with(cipher, &cleartext)
 }
 
-fn find_prefix_len(oracle_fn: fn(&[u8]) -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError>,
-                   blocksize: usize)
-                   -> usize {
+fn find_prefix_len<F>(oracle_fn: F, blocksize: usize) -> usize
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
+{
 
     // insert 4 blocks and look for the first repeated two blocks.  We know that
     // the prefix starts before there.  We need four, since three could
@@ -49,7 +55,7 @@ fn find_prefix_len(oracle_fn: fn(&[u8]) -> Result<Vec<u8>, symmetriccipher::Symm
     // We now have an upper bound for the prefix len.  Add two blocks known
     // value to the end, plus blocksize..0 bytes.
     let mut end_of_prefix_len = 0;
-    for i in 0..(blocksize - 1) {
+    for i in 0..blocksize {
         let ciphertext = oracle_fn(&vec![1u8; 2*blocksize+i]).unwrap();
         let first_block_start = prefix_blocks_with_padding * blocksize;
         let second_block_start = (prefix_blocks_with_padding + 1) * blocksize;
@@ -63,22 +69,25 @@ fn find_prefix_len(oracle_fn: fn(&[u8]) -> Result<Vec<u8>, symmetriccipher::Symm
     (prefix_blocks_with_padding - 1) * blocksize + (blocksize - end_of_prefix_len)
 }
 
-pub fn challenge14() {
-    let blocksize = challenge12::find_blocksize(oracle);
-    let prefix_len = find_prefix_len(oracle, blocksize);
+// the whole attack, against any oracle
+fn crack<F>(oracle_fn: F) -> (usize, usize, bool, Vec<u8>)
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
+{
+    let blocksize = challenge12::find_blocksize(&oracle_fn);
+    let prefix_len = find_prefix_len(&oracle_fn, blocksize);
 
     // wrap oracle with a closure that prefixes enough bytes to create even
     // blocks out of the oracle's prefix, then chops off those front blocks from
     // the cipertext, leaving only the enciphered attacking bytes at the
     // beginning of the ciphertext.
     let oracle_without_prefix =
-        |cleartext: &[u8]| -> Result<Vec<u8>, symmetriccipher::SymmetricCipherError> {
+        |cleartext: &[u8]| -> Result<Vec<u8>, CipherError> {
             let num_round_up_bytes = blocksize - (prefix_len % blocksize);
             let cleartext_with_choppable_prefix = (0..num_round_up_bytes)
                 .map(|_| 0u8)
                 .chain(cleartext.iter().cloned())
                 .collect::<Vec<_>>();
-            let mut ciphertext = oracle(&cleartext_with_choppable_prefix).unwrap();
+            let mut ciphertext = try!(oracle_fn(&cleartext_with_choppable_prefix));
             Ok(ciphertext.split_off(prefix_len + num_round_up_bytes))
         };
 
@@ -87,10 +96,49 @@ pub fn challenge14() {
     let suffix = challenge12::crack_ecb_with_known_blocksize_and_suffix(&oracle_without_prefix,
                                                                         blocksize,
                                                                         suffix_length);
+    (blocksize, prefix_len, using_ecb, suffix.unwrap())
+}
 
+pub fn challenge14() {
+    let (blocksize, prefix_len, using_ecb, suffix) = crack(oracle);
     println!("blocksize = {} ", blocksize);
     println!("prefix len = {} ", prefix_len);
     println!("is ecb {}", using_ecb);
-    println!("suffix len {}", suffix_length);
-    println!("suffix = {}", String::from_utf8_lossy(&suffix.unwrap()));
+    println!("suffix len {}", suffix.len());
+    println!("suffix = {}", String::from_utf8_lossy(&suffix));
+
+    // and again with 8 byte blocks
+    let des = Des::new(b"8bytekey").unwrap();
+    let triple_des = TripleDes::new(b"three keys, 24 bytes!!!!").unwrap();
+    let (blocksize, prefix_len, using_ecb, suffix) = crack(|data: &[u8]| oracle_with(&des, data));
+    println!("DES: blocksize = {} prefix len = {} is ecb {} suffix len {}",
+             blocksize,
+             prefix_len,
+             using_ecb,
+             suffix.len());
+    let (blocksize, prefix_len, using_ecb, suffix) =
+        crack(|data: &[u8]| oracle_with(&triple_des, data));
+    println!("3DES: blocksize = {} prefix len = {} is ecb {} suffix len {}",
+             blocksize,
+             prefix_len,
+             using_ecb,
+             suffix.len());
+}
+
+#[test]
+fn test_other_block_sizes() {
+    use blockcipher::ToyCipher;
+
+    let des = Des::new(b"8bytekey").unwrap();
+    let (blocksize, prefix_len, using_ecb, suffix) = crack(|data: &[u8]| oracle_with(&des, data));
+    assert_eq!((blocksize, prefix_len, using_ecb), (8, 37, true));
+    assert!(String::from_utf8_lossy(&suffix).starts_with("Rollin' in my 5.0"));
+
+    for blocksize in [3, 4].iter() {
+        let cipher = ToyCipher::new(b"secret", *blocksize).unwrap();
+        let (found_blocksize, prefix_len, _, suffix) =
+            crack(|data: &[u8]| oracle_with(&cipher, data));
+        assert_eq!((found_blocksize, prefix_len), (*blocksize, 37));
+        assert_eq!(suffix.len(), 138);
+    }
 }
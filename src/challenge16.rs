@@ -1,6 +1,6 @@
 use aes::{decrypt_cbc, encrypt_cbc};
 use bytes::pad;
-use crypto::symmetriccipher;
+use aes::CipherError;
 
 static KEY: [u8; 16] = [1; 16];
 static IV: [u8; 16] = [0; 16];
@@ -41,7 +41,7 @@ fn encryptor(userdata: &[u8]) -> Vec<u8> {
 }
 
 
-fn is_admin(ciphertext: &[u8]) -> Result<bool, symmetriccipher::SymmetricCipherError> {
+fn is_admin(ciphertext: &[u8]) -> Result<bool, CipherError> {
     let cleartext = try!(decrypt_cbc(ciphertext, &KEY, &IV));
     Ok(contains(&cleartext, b";admin=true;"))
 }
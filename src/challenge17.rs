@@ -1,17 +1,23 @@
-use aes::{encrypt_cbc, decrypt_cbc};
+use aes::{encrypt_cbc, decrypt_cbc_in_place_with, encrypt_cbc_with};
+use aescore::{Aes, BLOCK_SIZE};
 use base64::decode;
-use bytes::{pad, unpad, valid_padding};
+use blockcipher::BlockCipher;
+use des::Des;
+use padding::{Padding, Pkcs7};
 use rand::distributions::{IndependentSample, Range};
 use rand;
 use std::cell::RefCell;
 use std::iter::once;
 
-const BLOCK_SIZE: usize = 16;
-
 // super-secret random values
-static KEY: [u8; BLOCK_SIZE] = [1; BLOCK_SIZE];
+static KEY: [u8; 16] = [1; 16];
 static IV: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
 
+lazy_static! {
+    // the oracle gets called thousands of times, so only expand the key once
+    static ref CIPHER: Aes = Aes::new(&KEY).unwrap();
+}
+
 // keep global count of every time we call ciphertext_padding_valid
 thread_local!(static NUM_COMPARISONS: RefCell<u32> = RefCell::new(0));
 
@@ -31,7 +37,7 @@ fn get_encrypted_string() -> (Vec<u8>, Vec<u8>) {
         MDAwMDA5aXRoIG15IHJhZy10b3AgZG93biBzbyBteSBoYWlyIGNhbiBibG93";
     let plaintexts: Vec<_> = plaintext_strings
         .split_whitespace()
-        .map(|x| encrypt_cbc(&pad(decode(x).unwrap(), BLOCK_SIZE), &KEY, &IV).unwrap())
+        .map(|x| encrypt_cbc(&Pkcs7.pad(decode(x).unwrap(), BLOCK_SIZE), &KEY, &IV).unwrap())
         .collect();
 
     // return a random one
@@ -42,35 +48,49 @@ fn get_encrypted_string() -> (Vec<u8>, Vec<u8>) {
     ((&plaintexts[i]).clone(), IV.to_vec())
 }
 
-// decrypt ciphertext and return if the cleartext is propertly padded
-fn ciphertext_padding_valid(ciphertext: &[u8], iv: &[u8]) -> bool {
+// Decrypt ciphertext and return if the cleartext is propertly padded.  The
+// attack only gets to call this, through a closure, so it works out the block
+// size from the length of the IV.
+fn ciphertext_padding_valid<C: BlockCipher, P: Padding>(cipher: &C,
+                                                        padding: &P,
+                                                        ciphertext: &[u8],
+                                                        iv: &[u8])
+                                                        -> bool {
     NUM_COMPARISONS.with(|num| {
                              let prev = *num.borrow();
                              *num.borrow_mut() = prev + 1;
                          });
 
-    match decrypt_cbc(ciphertext, &KEY, &iv) {
-        Ok(cleartext) => valid_padding(&cleartext, BLOCK_SIZE),
+    let mut cleartext = ciphertext.to_vec();
+    match decrypt_cbc_in_place_with(cipher, &mut cleartext, iv) {
+        Ok(()) => padding.is_valid(&cleartext, cipher.block_size()),
         Err(_) => false,
     }
 }
 
 // create a block that will turn into a validly padded block when xor'ed with
 // the next cleartext (if guess is right).  cleartext is 'cleartext so far'
-fn assemble_attack_block(pre_block: &[u8], cleartext: &[u8], guess: u8) -> Vec<u8> {
+fn assemble_attack_block<P: Padding>(padding: &P,
+                                     pre_block: &[u8],
+                                     cleartext: &[u8],
+                                     guess: u8)
+                                     -> Vec<u8> {
     // n is the padding we're working on
+    let block_size = pre_block.len();
     let n = cleartext.len() + 1;
+    let pad = padding.padding_bytes(n, block_size);
 
     // which byte are we decrypting?
-    let guess_pos = BLOCK_SIZE - n;
+    let guess_pos = block_size - n;
 
     // set up attack_block to be:
     // [pre_block] + [pre_block ^ pad ^ guess] + [pre_block ^ pad ^ cleartext]
-    let xored_guess = once(pre_block[guess_pos] ^ (n as u8) ^ guess);
+    let xored_guess = once(pre_block[guess_pos] ^ pad[0] ^ guess);
     let xored_tail = pre_block[guess_pos + 1..]
         .iter()
         .zip(cleartext)
-        .map(|(a, b)| a ^ b ^ (n as u8));
+        .zip(pad[1..].iter())
+        .map(|((a, b), p)| a ^ b ^ p);
     pre_block[0..guess_pos]
         .iter()
         .cloned()
@@ -79,10 +99,15 @@ fn assemble_attack_block(pre_block: &[u8], cleartext: &[u8], guess: u8) -> Vec<u
         .collect::<Vec<_>>()
 }
 
-pub fn decrypt_remaining_block_one(attack_ciphertext: &[u8],
-                                   iv: &[u8],
-                                   cleartext: &[u8])
-                                   -> Option<Vec<u8>> {
+pub fn decrypt_remaining_block_one<P, F>(padding: &P,
+                                         oracle_fn: &F,
+                                         attack_ciphertext: &[u8],
+                                         iv: &[u8],
+                                         cleartext: &[u8])
+                                         -> Option<Vec<u8>>
+    where P: Padding,
+          F: Fn(&[u8], &[u8]) -> bool
+{
 
     // ascii first, and reverse the order of padding bytes - 1 is potential
     // mis-guess that will slow down the decryption process, so do it after the
@@ -92,18 +117,20 @@ pub fn decrypt_remaining_block_one(attack_ciphertext: &[u8],
             .chain((0..65).rev())
             .chain(123..255) {
 
-        let attack_iv = assemble_attack_block(iv, cleartext, guess);
+        let attack_iv = assemble_attack_block(padding, iv, cleartext, guess);
 
-        if ciphertext_padding_valid(&attack_ciphertext, &attack_iv) {
+        if oracle_fn(&attack_ciphertext, &attack_iv) {
             let mut updated_cleartext = cleartext.to_vec();
             updated_cleartext.insert(0, guess);
 
             // we've got our block
-            if updated_cleartext.len() == BLOCK_SIZE {
+            if updated_cleartext.len() == iv.len() {
                 return Some(updated_cleartext);
             }
             // more to do
-            else if let Some(c) = decrypt_remaining_block_one(&attack_ciphertext,
+            else if let Some(c) = decrypt_remaining_block_one(padding,
+                                                                oracle_fn,
+                                                                &attack_ciphertext,
                                                                 &iv,
                                                                 &updated_cleartext) {
                 return Some(c);
@@ -114,24 +141,30 @@ pub fn decrypt_remaining_block_one(attack_ciphertext: &[u8],
     None
 }
 
-pub fn decrypt_block(block_num: usize,
-                     ciphertext: &[u8],
-                     iv: &[u8],
-                     block_cleartext: &[u8])
-                     -> Option<Vec<u8>> {
+pub fn decrypt_block<P, F>(padding: &P,
+                           oracle_fn: &F,
+                           block_num: usize,
+                           ciphertext: &[u8],
+                           iv: &[u8],
+                           block_cleartext: &[u8])
+                           -> Option<Vec<u8>>
+    where P: Padding,
+          F: Fn(&[u8], &[u8]) -> bool
+{
 
     // first block needs special handling, since we're varying the IV, not the
     // previous block
     assert!(block_num > 0);
 
-    let prev_block_start = (block_num - 1) * BLOCK_SIZE;
-    let prev_block = &ciphertext[prev_block_start..prev_block_start + BLOCK_SIZE];
+    let block_size = iv.len();
+    let prev_block_start = (block_num - 1) * block_size;
+    let prev_block = &ciphertext[prev_block_start..prev_block_start + block_size];
 
     // chop off ciphertext after the current block
     let mut attack_ciphertext = ciphertext
         .iter()
         .cloned()
-        .take((block_num + 1) * BLOCK_SIZE)
+        .take((block_num + 1) * block_size)
         .collect::<Vec<_>>();
 
     // ascii first, and reverse the order of padding bytes - 1 is potential
@@ -142,19 +175,24 @@ pub fn decrypt_block(block_num: usize,
             .chain(123..255) {
 
         // attack_ciphertext = [previous blocks] [attack_prev_block] [current block]
-        let attack_prev_block = assemble_attack_block(prev_block, &block_cleartext, guess);
+        let attack_prev_block = assemble_attack_block(padding, prev_block, &block_cleartext, guess);
 
-        for i in 0..BLOCK_SIZE {
+        for i in 0..block_size {
             attack_ciphertext[i + prev_block_start] = attack_prev_block[i];
         }
 
-        if ciphertext_padding_valid(&attack_ciphertext, &iv) {
+        if oracle_fn(&attack_ciphertext, &iv) {
             let mut cleartext = block_cleartext.to_vec();
             cleartext.insert(0, guess);
 
-            if cleartext.len() == BLOCK_SIZE {
+            if cleartext.len() == block_size {
                 return Some(cleartext);
-            } else if let Some(c) = decrypt_block(block_num, ciphertext, iv, &cleartext) {
+            } else if let Some(c) = decrypt_block(padding,
+                                                  oracle_fn,
+                                                  block_num,
+                                                  ciphertext,
+                                                  iv,
+                                                  &cleartext) {
                 return Some(c);
             }
         }
@@ -163,8 +201,11 @@ pub fn decrypt_block(block_num: usize,
     None
 }
 
-pub fn decrypt_block_one(ciphertext: &[u8], iv: &[u8]) -> Vec<u8> {
-    let attack_ciphertext = &ciphertext[0..BLOCK_SIZE];
+pub fn decrypt_block_one<P, F>(padding: &P, oracle_fn: &F, ciphertext: &[u8], iv: &[u8]) -> Vec<u8>
+    where P: Padding,
+          F: Fn(&[u8], &[u8]) -> bool
+{
+    let attack_ciphertext = &ciphertext[0..iv.len()];
 
     // ascii first, and reverse the order of padding bytes - 1 is potential
     // miss-guess that will slow down the decryption process
@@ -172,10 +213,14 @@ pub fn decrypt_block_one(ciphertext: &[u8], iv: &[u8]) -> Vec<u8> {
             .chain((65..97))
             .chain((0..65).rev())
             .chain(123..255) {
-        let attack_iv = assemble_attack_block(&iv, &Vec::new(), guess);
-        if ciphertext_padding_valid(&attack_ciphertext, &attack_iv) {
+        let attack_iv = assemble_attack_block(padding, &iv, &Vec::new(), guess);
+        if oracle_fn(&attack_ciphertext, &attack_iv) {
             let cleartext = vec![guess];
-            if let Some(c) = decrypt_remaining_block_one(&attack_ciphertext, &iv, &cleartext) {
+            if let Some(c) = decrypt_remaining_block_one(padding,
+                                                         oracle_fn,
+                                                         &attack_ciphertext,
+                                                         &iv,
+                                                         &cleartext) {
                 return c;
             }
         }
@@ -184,10 +229,19 @@ pub fn decrypt_block_one(ciphertext: &[u8], iv: &[u8]) -> Vec<u8> {
     panic!("Decrypt failed")
 }
 
-pub fn decrypt_remaining_blocks(ciphertext: &[u8], iv: &[u8], block_1_cleartext: &[u8]) -> Vec<u8> {
+pub fn decrypt_remaining_blocks<P, F>(padding: &P,
+                                      oracle_fn: &F,
+                                      ciphertext: &[u8],
+                                      iv: &[u8],
+                                      block_1_cleartext: &[u8])
+                                      -> Vec<u8>
+    where P: Padding,
+          F: Fn(&[u8], &[u8]) -> bool
+{
     let mut cleartext = block_1_cleartext.to_vec();
-    for block_num in 1..ciphertext.len() / BLOCK_SIZE {
-        let clear_block = &mut decrypt_block(block_num, ciphertext, iv, &vec![]).unwrap();
+    for block_num in 1..ciphertext.len() / iv.len() {
+        let clear_block = &mut decrypt_block(padding, oracle_fn, block_num, ciphertext, iv, &vec![])
+                                   .unwrap();
         cleartext.append(clear_block);
     }
 
@@ -207,18 +261,72 @@ pub fn challenge17() {
     //
     // Why?
 
-    let block_one = decrypt_block_one(&ciphertext, &iv);
-    let cleartext = decrypt_remaining_blocks(&ciphertext, &iv, &block_one);
-    println!("{}", String::from_utf8_lossy(&unpad(cleartext)));
+    let cleartext = decrypt(&Pkcs7, &*CIPHER, &ciphertext, &iv);
+    println!("{}", String::from_utf8_lossy(&Pkcs7.unpad(cleartext, BLOCK_SIZE).unwrap()));
     NUM_COMPARISONS.with(|num| {
                              println!("{} comparisons to find", *num.borrow());
                          });
+
+    // and again with 8 byte blocks
+    let des = Des::new(b"8bytekey").unwrap();
+    let des_iv = [0u8; 8];
+    let cleartext = Pkcs7.pad(b"Cooking MC's like a pound of bacon".to_vec(), 8);
+    let ciphertext = encrypt_cbc_with(&des, &cleartext, &des_iv).unwrap();
+    let decrypted = decrypt(&Pkcs7, &des, &ciphertext, &des_iv);
+    println!("DES: {}", String::from_utf8_lossy(&Pkcs7.unpad(decrypted, 8).unwrap()));
+}
+
+// the whole attack, against a server decrypting with `cipher`
+fn decrypt<C: BlockCipher, P: Padding>(padding: &P,
+                                       cipher: &C,
+                                       ciphertext: &[u8],
+                                       iv: &[u8])
+                                       -> Vec<u8> {
+    let oracle_fn = |ciphertext: &[u8], iv: &[u8]| {
+        ciphertext_padding_valid(cipher, padding, ciphertext, iv)
+    };
+    let block_one = decrypt_block_one(padding, &oracle_fn, ciphertext, iv);
+    decrypt_remaining_blocks(padding, &oracle_fn, ciphertext, iv, &block_one)
 }
 
 #[test]
 fn test() {
     for _ in 0..100 {
         let (ciphertext, iv) = get_encrypted_string();
-        assert!(ciphertext_padding_valid(&ciphertext, &iv));
+        assert!(ciphertext_padding_valid(&*CIPHER, &Pkcs7, &ciphertext, &iv));
     }
 }
+
+// The same attack works against any padding scheme the oracle checks
+// strictly.  ISO 10126 only checks the last byte, and zero padding can't be
+// checked at all, so those only ever leak the last byte of a block.
+#[cfg(test)]
+fn attack_with_padding<C: BlockCipher, P: Padding>(cipher: &C, padding: &P) {
+    let block_size = cipher.block_size();
+    let cleartext = b"Cooking MC's like a pound of bacon".to_vec();
+    let iv = vec![0u8; block_size];
+    let ciphertext = encrypt_cbc_with(cipher, &padding.pad(cleartext.clone(), block_size), &iv)
+        .unwrap();
+
+    let decrypted = decrypt(padding, cipher, &ciphertext, &iv);
+    assert_eq!(padding.unpad(decrypted, block_size).unwrap(), cleartext);
+}
+
+#[test]
+fn test_padding_schemes() {
+    use padding::{AnsiX923, Iso7816};
+
+    attack_with_padding(&*CIPHER, &Pkcs7);
+    attack_with_padding(&*CIPHER, &AnsiX923);
+    attack_with_padding(&*CIPHER, &Iso7816);
+}
+
+#[test]
+fn test_other_block_sizes() {
+    use blockcipher::ToyCipher;
+    use des::TripleDes;
+
+    attack_with_padding(&Des::new(b"8bytekey").unwrap(), &Pkcs7);
+    attack_with_padding(&TripleDes::new(&[7u8; 16]).unwrap(), &Pkcs7);
+    attack_with_padding(&ToyCipher::new(b"secret", 5).unwrap(), &Pkcs7);
+}
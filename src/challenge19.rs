@@ -1,15 +1,26 @@
 use base64::decode;
-use aes::encrypt_ctr;
+use aescore::Aes;
+use bytes::random_bytes;
+use chacha20::ChaCha20;
+use fixednonce::{encrypt_fixed_nonce, load_ciphertexts};
+use language::word_list;
+use rc4::Rc4;
+use salsa20::Salsa20;
 use std::cmp::min;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
 use std::sync::Mutex;
+use stream::CtrStream;
 
 lazy_static! {
 
     // load a set of sorted words into a vector
-    static ref SORTED_ENGLISH_WORDS: Vec<&'static str> = {
+    static ref SORTED_ENGLISH_WORDS: Vec<String> = {
         include_str!("google-10000-english-usa-sorted.txt")
         //include_str!("words-sorted.txt")
             .lines()
+            .map(|w| w.to_string())
             .collect::<Vec<_>>()
         };
 
@@ -22,20 +33,19 @@ fn is_number(guess: &str) -> bool {
     guess.chars().all(|c| numbers.contains(c))
 }
 
-fn is_english_word(guess: &str) -> bool {
+// `words` must be sorted and lower case, like language::word_list gives
+fn is_word(words: &[String], guess: &str) -> bool {
     let lc_guess = guess.to_lowercase();
-    SORTED_ENGLISH_WORDS
-        .binary_search_by(|&w| w.cmp(&lc_guess))
-        .is_ok()
+    words.binary_search(&lc_guess).is_ok()
 }
 
-fn is_english_prefix(guess: &str) -> bool {
+fn is_word_prefix(words: &[String], guess: &str) -> bool {
     let lc_guess = guess.to_lowercase();
-    let guess_len = guess.len();
-    SORTED_ENGLISH_WORDS
-        .binary_search_by(|&w| {
+    let guess_len = lc_guess.len();
+    words
+        .binary_search_by(|w| {
                               let w_len = min(w.len(), guess_len);
-                              w[..w_len].cmp(&lc_guess)
+                              w.as_bytes()[..w_len].cmp(lc_guess.as_bytes())
                           })
         .is_ok()
 }
@@ -52,7 +62,7 @@ fn is_valid_word_character(byte: &u8) -> bool {
     b"etaoinshrdlcumwfgypbvkjxqz0123456789ETAOINSHRDLCUMWFGYPBVKJXQZ".contains(byte)
 }
 
-fn good_key_so_far(key_so_far: &[u8], ciphertext: &[u8]) -> bool {
+fn good_key_so_far(words: &[String], key_so_far: &[u8], ciphertext: &[u8]) -> bool {
 
     let cleartext = key_so_far
         .iter()
@@ -80,7 +90,7 @@ fn good_key_so_far(key_so_far: &[u8], ciphertext: &[u8]) -> bool {
         // multiple spaces, or an actual word
         let last_word_str: String = last_word.into_iter().collect();
         let rc = last_word_str.len() == 0 || is_number(&last_word_str) ||
-                 is_english_word(&last_word_str);
+                 is_word(words, &last_word_str);
         return rc;
     } else if key_so_far.len() == ciphertext.len() {
 
@@ -96,7 +106,7 @@ fn good_key_so_far(key_so_far: &[u8], ciphertext: &[u8]) -> bool {
         // multiple spaces, or an actual word
         let last_word_str: String = last_word.into_iter().collect();
         let rc = last_word_str.len() == 0 || is_number(&last_word_str) ||
-                 is_english_word(&last_word_str);
+                 is_word(words, &last_word_str);
         return rc;
     } else {
         // in the middle of a word - is the word we are building a possible word?
@@ -109,12 +119,13 @@ fn good_key_so_far(key_so_far: &[u8], ciphertext: &[u8]) -> bool {
         last_word.reverse();
         let last_word_str: String = last_word.into_iter().collect();
 
-        let rc = is_number(&last_word_str) || is_english_prefix(&last_word_str);
+        let rc = is_number(&last_word_str) || is_word_prefix(words, &last_word_str);
         return rc;
     }
 }
 
 fn solve_repeated_pad(ciphertexts: &Vec<Vec<u8>>,
+                      words: &[String],
                       target_index: usize,
                       key_so_far: &mut Vec<u8>)
                       -> Option<Vec<u8>> {
@@ -136,14 +147,14 @@ fn solve_repeated_pad(ciphertexts: &Vec<Vec<u8>>,
         key_so_far.push(key_byte);
         if ciphertexts
                .iter()
-               .all(|c| good_key_so_far(key_so_far, c)) {
+               .all(|c| good_key_so_far(words, key_so_far, c)) {
 
             // keep track of our best solution out-of-band
             if key_so_far.len() > BEST_ANSWER.lock().unwrap().len() {
                 *(BEST_ANSWER.lock().unwrap()) = key_so_far.clone();
             }
 
-            if let Some(answer) = solve_repeated_pad(ciphertexts, target_index, key_so_far) {
+            if let Some(answer) = solve_repeated_pad(ciphertexts, words, target_index, key_so_far) {
                 return Some(answer);
             }
         }
@@ -153,20 +164,63 @@ fn solve_repeated_pad(ciphertexts: &Vec<Vec<u8>>,
     None
 }
 
+// The solvers only ever see the ciphertexts, so any stream cipher will do.
+// `source` picks one - aes-ctr, salsa20, chacha20 or rc4 - or names a file of
+// base64 ciphertexts to break instead.
+pub fn fixed_nonce_ciphertexts(source: &str, cleartexts: &[Vec<u8>]) -> io::Result<Vec<Vec<u8>>> {
+    println!("ciphertexts from {}", source);
+    let key = random_bytes(32);
+    let ciphertexts = match source {
+        "aes-ctr" => {
+            encrypt_fixed_nonce(cleartexts, || {
+                CtrStream::new(Aes::new(b"YELLOW SUBMARINE").unwrap(), 0, ()).unwrap()
+            })
+        }
+        "salsa20" => encrypt_fixed_nonce(cleartexts, || Salsa20::new(&key, &[0; 8]).unwrap()),
+        "chacha20" => {
+            encrypt_fixed_nonce(cleartexts, || ChaCha20::new(&key, &[0; 12], 0).unwrap())
+        }
+        "rc4" => encrypt_fixed_nonce(cleartexts, || Rc4::new(&key).unwrap()),
+        path => return load_ciphertexts(path),
+    };
+    ciphertexts.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", e)))
+}
+
+// The second argument picks the source of the ciphertexts, aes-ctr by default.
+pub fn ciphertext_source() -> String {
+    env::args().nth(2).unwrap_or("aes-ctr".to_string())
+}
+
 // Note: because all the cleartexts are not the same length, and we're being very permissive with
 // what constitutes a "word", the ends of the longest ciphertexts are harder to find.  The challenge
 // said specifically "don't overthink this", so good enough.
 pub fn challenge19() {
-    let key = b"YELLOW SUBMARINE";
-    let nonce = 0;
     let cleartexts = include_str!("challenge-19.dat")
         .lines()
         .map(|x| decode(x).unwrap())
         .collect::<Vec<_>>();
-    let ciphertexts = cleartexts
-        .iter()
-        .map(|cleartext| encrypt_ctr(cleartext, key, &nonce).unwrap())
-        .collect::<Vec<_>>();
+    let ciphertexts = match fixed_nonce_ciphertexts(&ciphertext_source(), &cleartexts) {
+        Ok(ciphertexts) => ciphertexts,
+        Err(e) => {
+            println!("couldn't load the ciphertexts: {}", e);
+            return;
+        }
+    };
+
+    // A third argument names a corpus to take the dictionary from, for
+    // cleartexts that aren't English.  The guesses are still ASCII letters, so
+    // words with accents won't be found.
+    let words = match env::args().nth(3) {
+        Some(path) => {
+            let mut corpus = String::new();
+            if let Err(e) = File::open(&path).and_then(|mut f| f.read_to_string(&mut corpus)) {
+                println!("couldn't load the dictionary: {}", e);
+                return;
+            }
+            word_list(&corpus)
+        }
+        None => SORTED_ENGLISH_WORDS.clone(),
+    };
 
     // find the longest of ciphertexts - when we decode that one, we're done
     let mut max_len = 0;
@@ -178,7 +232,7 @@ pub fn challenge19() {
         }
     }
 
-    if let Some(key) = solve_repeated_pad(&ciphertexts, longest_ciphertext, &mut vec![]) {
+    if let Some(key) = solve_repeated_pad(&ciphertexts, &words, longest_ciphertext, &mut vec![]) {
         println!("key = {:?}", &key);
         for c in ciphertexts {
             let cleartext = key.iter()
@@ -205,8 +259,14 @@ pub fn challenge19() {
 
 #[test]
 fn test_prefix() {
-    assert!(is_english_prefix("a"));
-    assert!(is_english_prefix("Az"));
-    assert!(is_english_prefix("Ei"));
-    assert!(!is_english_prefix("zt"));
+    assert!(is_word_prefix(&SORTED_ENGLISH_WORDS, "a"));
+    assert!(is_word_prefix(&SORTED_ENGLISH_WORDS, "Az"));
+    assert!(is_word_prefix(&SORTED_ENGLISH_WORDS, "Ei"));
+    assert!(!is_word_prefix(&SORTED_ENGLISH_WORDS, "zt"));
+
+    // any language's words, accents and all
+    let words = word_list("Alle Menschen sind frei und gleich an Würde und Rechten geboren.");
+    assert!(is_word(&words, "Würde"));
+    assert!(is_word_prefix(&words, "wü"));
+    assert!(!is_word(&words, "wurde"));
 }
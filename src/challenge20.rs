@@ -1,45 +1,43 @@
 use bytes::*;
-use aes::encrypt_ctr;
 use base64::decode;
-use challenge6::transpose;
+use challenge19::{ciphertext_source, fixed_nonce_ciphertexts};
+use fixednonce::break_fixed_nonce;
 
+// A repeating xor is a fixed nonce keystream `keysize` bytes long, with each
+// key length run of the ciphertext as one of the messages.
+#[allow(dead_code)]
 pub fn solve_repeated_xor(ciphertext: &[u8], keysize: usize) -> Vec<u8> {
-    let mut key = Vec::new();
-    for c in transpose(&ciphertext, keysize) {
-        let (k, _, _) = most_english_xor(&c).unwrap();
-        key.push(k);
-    }
-    key
+    let rows = ciphertext.chunks(keysize).map(|c| c.to_vec()).collect::<Vec<_>>();
+    break_fixed_nonce(&rows)
 }
 
-// concatenate all of the first prefix_len bytes of vecs into one vec
-fn concat_prefixes(vecs: &Vec<Vec<u8>>, prefix_len: usize) -> Vec<u8> {
-    let mut concatted: Vec<u8> = Vec::new();
-    for c in vecs {
-        concatted.extend(c[0..prefix_len].iter());
-    }
-    concatted
-}
-
-// Note that this is essentially the same as challenge 6.
+// Note that this is essentially the same as challenge 6, with each column of
+// the ciphertexts solved as a single byte xor.  Past the end of the shortest
+// ciphertext there are fewer and fewer bytes in a column, so the ends of the
+// longer lines come out garbled.
 pub fn challenge20() {
-    let key = b"YELLOW SUBMARINE";
-    let nonce = 0;
     let cleartexts = include_str!("challenge-20.dat")
         .lines()
         .map(|x| decode(x).unwrap())
         .collect::<Vec<_>>();
-    let ciphertexts = cleartexts
-        .iter()
-        .map(|cleartext| encrypt_ctr(cleartext, key, &nonce).unwrap())
-        .collect::<Vec<_>>();
-
-    let shortest_ciphertext_len = ciphertexts.iter().map(|c| c.len()).min().unwrap();
-    let concatted_ciphertexts = concat_prefixes(&ciphertexts, shortest_ciphertext_len);
-    let key = solve_repeated_xor(&concatted_ciphertexts, shortest_ciphertext_len);
+    let ciphertexts = match fixed_nonce_ciphertexts(&ciphertext_source(), &cleartexts) {
+        Ok(ciphertexts) => ciphertexts,
+        Err(e) => {
+            println!("couldn't load the ciphertexts: {}", e);
+            return;
+        }
+    };
 
+    let keystream = break_fixed_nonce(&ciphertexts);
     for c in &ciphertexts {
-        println!("{}",
-                 String::from_utf8_lossy(&repeat_xor(&c, &key)[0..shortest_ciphertext_len]));
+        println!("{}", String::from_utf8_lossy(&inplace_xor(c.clone(), &keystream)));
     }
 }
+
+#[test]
+fn test_solve_repeated_xor() {
+    use base64::{decode_config, MIME};
+
+    let ciphertext = decode_config(include_str!("challenge-6.dat"), MIME).unwrap();
+    assert_eq!(solve_repeated_xor(&ciphertext, 29), b"Terminator X: Bring the noise");
+}
@@ -0,0 +1,49 @@
+use base64::{decode_config, MIME};
+use aes::{decrypt_ecb_padded, encrypt_ctr, edit};
+use bytes::{random_bytes, repeat_xor};
+
+lazy_static! {
+    // super-secret random key
+    static ref KEY: Vec<u8> = random_bytes(16);
+}
+
+static NONCE: u64 = 0;
+
+// challenge 7's cleartext, encrypted under CTR with the secret key
+fn get_ciphertext() -> Vec<u8> {
+    let ecb_ciphertext = decode_config(include_str!("challenge-7.dat"), MIME).unwrap();
+    let cleartext = decrypt_ecb_padded(&ecb_ciphertext, b"YELLOW SUBMARINE").unwrap();
+    encrypt_ctr(&cleartext, &KEY, &NONCE).unwrap()
+}
+
+// the "API" the attacker gets - seek into the ciphertext and replace some
+// cleartext, getting back the new ciphertext
+fn edit_oracle(ciphertext: &[u8], offset: usize, newtext: &[u8]) -> Vec<u8> {
+    let mut edited = ciphertext.to_vec();
+    edit(&mut edited, &KEY, &NONCE, offset, newtext).unwrap();
+    edited
+}
+
+// Rewriting the whole message as zeros hands back the raw keystream, which
+// decrypts the original.
+fn recover_cleartext(ciphertext: &[u8]) -> Vec<u8> {
+    let keystream = edit_oracle(ciphertext, 0, &vec![0u8; ciphertext.len()]);
+    repeat_xor(ciphertext, &keystream)
+}
+
+pub fn challenge25() {
+    let ciphertext = get_ciphertext();
+    let cleartext = recover_cleartext(&ciphertext);
+    println!("{}", String::from_utf8_lossy(&cleartext));
+}
+
+#[test]
+fn test() {
+    let ciphertext = get_ciphertext();
+    let ecb_ciphertext = decode_config(include_str!("challenge-7.dat"), MIME).unwrap();
+    let expected = decrypt_ecb_padded(&ecb_ciphertext, b"YELLOW SUBMARINE").unwrap();
+    assert_eq!(recover_cleartext(&ciphertext), expected);
+
+    // editing in the ciphertext itself also spits out the cleartext
+    assert_eq!(edit_oracle(&ciphertext, 0, &ciphertext), expected);
+}
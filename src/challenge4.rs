@@ -1,5 +1,7 @@
 use bytes::*;
 use hexstring::*;
+use scoring::ChiSquared;
+use std::cmp::Ordering;
 
 static CIPHERTEXTS: &'static str = "0e3647e8592d35514a081243582536ed3de6734059001e3f535ce6271032
 334b041de124f73c18011a50e608097ac308ecee501337ec3e100854201d
@@ -330,25 +332,23 @@ e03555453d1e31775f37331823164c341c09e310463438481019fb0b12fa
 32042f46431d2c44607934ed180c1028136a5f2b26092e3b2c4e2930585a";
 
 pub fn challenge4() {
-    let answers = CIPHERTEXTS
+    let answer = CIPHERTEXTS
             .lines()
             .enumerate()
 
             // decode from hexstring and return linenum
             .map(|(num, l)| (num, fromhex(l).unwrap()))
 
-            // get linenum, key, rating and best cleartext
+            // get linenum, key, rating and best cleartext, going down the
+            // rankings past any with control characters in
             .map(|(num, l)| {
-                let (k, rating, cleartext) = most_english_xor(&l).unwrap();
+                let (k, rating, cleartext) = most_likely_text_xor(&l, &ChiSquared::english(), 4)
+                    .unwrap();
                 (num, k, rating, cleartext)
                 })
 
-            // keep highly rated lines
-            .filter(|&(_, _, rating, _)| rating > 0.9);
+            // the one line that really was encrypted rates far above the rest
+            .max_by(|x, y| x.2.partial_cmp(&y.2).unwrap_or(Ordering::Equal));
 
-    let string_answers = answers
-        .map(|(l, k, rating, cleartext)| (l, k, rating, cleartext))
-        .collect::<Vec<_>>();
-
-    println!("{:?}", string_answers);
+    println!("{:?}", answer);
 }
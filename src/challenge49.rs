@@ -0,0 +1,207 @@
+use bytes::{pad, random_bytes, repeat_xor};
+use mac::{cbc_mac, verify_mac};
+
+lazy_static! {
+    // shared between the bank's web client and its API server
+    static ref KEY: Vec<u8> = random_bytes(16);
+}
+
+static VICTIM: u32 = 2;
+static ATTACKER: u32 = 3;
+
+#[derive(Debug, PartialEq)]
+struct Transfer {
+    from: u32,
+    to: u32,
+    amount: u64,
+}
+
+// Version 1 of the API: a request is message || IV || MAC, where the message
+// is "from=#{from_id}&to=#{to_id}&amount=#{amount}".  The client only signs
+// transfers from the logged in user's own account.
+fn sign_transfer(from: u32, to: u32, amount: u64) -> Vec<u8> {
+    let message = format!("from={}&to={}&amount={}", from, to, amount).into_bytes();
+    let iv = random_bytes(16);
+    let mac = cbc_mac(&pad(message.clone(), 16), &KEY, &iv).unwrap();
+
+    let mut request = message;
+    request.extend(iv);
+    request.extend(mac);
+    request
+}
+
+// split off and check the MAC, handing back the message
+fn verify_request(request: &[u8], iv: Option<&[u8]>) -> Option<Vec<u8>> {
+    let iv_len = if iv.is_some() { 0 } else { 16 };
+    if request.len() < 16 + iv_len {
+        return None;
+    }
+    let mac_start = request.len() - 16;
+    let message = &request[..mac_start - iv_len];
+    let iv = iv.unwrap_or(&request[mac_start - iv_len..mac_start]);
+    let expected = cbc_mac(&pad(message.to_vec(), 16), &KEY, iv).unwrap();
+    if verify_mac(&expected, &request[mac_start..]) {
+        Some(message.to_vec())
+    } else {
+        None
+    }
+}
+
+// the API server
+fn process_transfer(request: &[u8]) -> Option<Transfer> {
+    let message = match verify_request(request, None) {
+        Some(message) => message,
+        None => return None,
+    };
+
+    let mut transfer = Transfer {
+        from: 0,
+        to: 0,
+        amount: 0,
+    };
+    for field in String::from_utf8_lossy(&message).split('&') {
+        let mut parts = field.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("from"), Some(v)) => transfer.from = v.parse().unwrap_or(0),
+            (Some("to"), Some(v)) => transfer.to = v.parse().unwrap_or(0),
+            (Some("amount"), Some(v)) => transfer.amount = v.parse().unwrap_or(0),
+            _ => (),
+        }
+    }
+    Some(transfer)
+}
+
+// The attacker signs a transfer from their own account to itself, then
+// changes the "from" in the first block.  The IV is xored into the first block
+// before it's encrypted, so making the same change to the IV leaves the MAC
+// alone.
+fn forge_transfer() -> Vec<u8> {
+    let mut request = sign_transfer(ATTACKER, ATTACKER, 1000000);
+    let original = format!("from={}", ATTACKER).into_bytes();
+    let forged = format!("from={}", VICTIM).into_bytes();
+    assert_eq!(original.len(), forged.len());
+
+    let iv_start = request.len() - 32;
+    let difference = repeat_xor(&original, &forged);
+    for (i, d) in difference.iter().enumerate() {
+        request[i] ^= *d;
+        request[iv_start + i] ^= *d;
+    }
+    request
+}
+
+// Version 2 fixes the IV at zero, so the request is just message || MAC, and
+// takes a list of transfers: "from=#{from_id}&tx_list=#{to}:#{amount}(;#{to}:#{amount})*"
+fn sign_transfers(from: u32, transfers: &[(u32, u64)]) -> Vec<u8> {
+    let tx_list: Vec<String> = transfers
+        .iter()
+        .map(|&(to, amount)| format!("{}:{}", to, amount))
+        .collect();
+    let message = format!("from={}&tx_list={}", from, tx_list.join(";")).into_bytes();
+    let mac = cbc_mac(&pad(message.clone(), 16), &KEY, &[0u8; 16]).unwrap();
+
+    let mut request = message;
+    request.extend(mac);
+    request
+}
+
+// The server skips any transaction it can't make sense of, which is what lets
+// the forgery's garbage block through.
+fn process_transfers(request: &[u8]) -> Option<Vec<Transfer>> {
+    let message = match verify_request(request, Some(&[0u8; 16])) {
+        Some(message) => message,
+        None => return None,
+    };
+
+    let message = String::from_utf8_lossy(&message).into_owned();
+    let mut fields = message.splitn(2, "&tx_list=");
+    let from = match fields.next() {
+        Some(f) if f.starts_with("from=") => f[5..].parse().unwrap_or(0),
+        _ => return None,
+    };
+
+    let mut transfers = Vec::new();
+    for tx in fields.next().unwrap_or("").split(';') {
+        let mut parts = tx.splitn(2, ':');
+        if let (Some(to), Some(amount)) = (parts.next(), parts.next()) {
+            if let (Ok(to), Ok(amount)) = (to.parse(), amount.parse()) {
+                transfers.push(Transfer {
+                                   from: from,
+                                   to: to,
+                                   amount: amount,
+                               });
+            }
+        }
+    }
+    Some(transfers)
+}
+
+// Length extension.  The victim's MAC is the CBC state at the end of their
+// (padded) message, so appending the attacker's own signed message with its
+// first block xored with that MAC puts the CBC state back to where the
+// attacker's message started from, and the attacker's MAC is valid for the
+// whole thing.  The first block of the appended message turns into garbage,
+// which the attacker's transaction has to come after.
+fn forge_transfers(captured: &[u8]) -> Vec<u8> {
+    let (victim_message, victim_mac) = captured.split_at(captured.len() - 16);
+
+    // the first block is "from=3&tx_list=3", so the real transfer is in the
+    // second
+    let attacker_request = sign_transfers(ATTACKER, &[(ATTACKER, 0), (ATTACKER, 1000000)]);
+    let (attacker_message, attacker_mac) = attacker_request.split_at(attacker_request.len() - 16);
+
+    let mut forged = pad(victim_message.to_vec(), 16);
+    forged.extend(repeat_xor(&attacker_message[..16], victim_mac));
+    forged.extend_from_slice(&attacker_message[16..]);
+    forged.extend_from_slice(attacker_mac);
+    forged
+}
+
+pub fn challenge49() {
+    let transfer = process_transfer(&forge_transfer()).unwrap();
+    println!("{:?}", transfer);
+
+    let captured = sign_transfers(VICTIM, &[(4, 100), (5, 250)]);
+    for transfer in process_transfers(&forge_transfers(&captured)).unwrap() {
+        println!("{:?}", transfer);
+    }
+}
+
+#[test]
+fn test() {
+    let request = sign_transfer(ATTACKER, 5, 100);
+    assert_eq!(process_transfer(&request),
+               Some(Transfer {
+                        from: ATTACKER,
+                        to: 5,
+                        amount: 100,
+                    }));
+
+    // changing the message without fixing up the IV is caught
+    let mut tampered = request.clone();
+    tampered[5] = b'2';
+    assert_eq!(process_transfer(&tampered), None);
+
+    assert_eq!(process_transfer(&forge_transfer()),
+               Some(Transfer {
+                        from: VICTIM,
+                        to: ATTACKER,
+                        amount: 1000000,
+                    }));
+}
+
+#[test]
+fn test_length_extension() {
+    let captured = sign_transfers(VICTIM, &[(4, 100), (5, 250)]);
+    let transfers = process_transfers(&captured).unwrap();
+    assert_eq!(transfers.len(), 2);
+
+    let transfers = process_transfers(&forge_transfers(&captured)).unwrap();
+    assert!(transfers.iter().all(|t| t.from == VICTIM));
+    assert_eq!(transfers.last(),
+               Some(&Transfer {
+                         from: VICTIM,
+                         to: ATTACKER,
+                         amount: 1000000,
+                     }));
+}
@@ -0,0 +1,54 @@
+use bytes::{pad, repeat_xor};
+use hexstring::tohex;
+use mac::cbc_mac;
+
+static KEY: &'static [u8; 16] = b"YELLOW SUBMARINE";
+static ORIGINAL: &'static [u8] = b"alert('MZA who was that?');\n";
+static PAYLOAD: &'static str = "alert('Ayo, the Wu is back!');";
+
+// CBC-MAC with a fixed key and IV, used as a hash
+fn hash(data: &[u8]) -> Vec<u8> {
+    cbc_mac(&pad(data.to_vec(), 16), KEY, &[0u8; 16]).unwrap()
+}
+
+// The payload, then a comment, padded out with spaces to a whole number of
+// blocks.  The hash state at the end of it is known, so the next block can be
+// chosen to put it back to zero - after that the original's blocks hash just
+// like they did originally.  The chosen block is garbage, but it's in the
+// comment, as long as it doesn't contain a line break.  Different amounts of
+// space give different garbage, so keep trying until one doesn't.
+fn forge(original: &[u8], payload: &str) -> Vec<u8> {
+    for extra_spaces in 0.. {
+        let mut forged = format!("{}{}//", payload, " ".repeat(extra_spaces)).into_bytes();
+        let len = (forged.len() + 15) / 16 * 16;
+        forged.resize(len, b' ');
+
+        let state = cbc_mac(&forged, KEY, &[0u8; 16]).unwrap();
+        let garbage = repeat_xor(&original[..16], &state);
+        if garbage.iter().any(|b| *b == b'\n' || *b == b'\r') {
+            continue;
+        }
+        forged.extend(garbage);
+        forged.extend_from_slice(&original[16..]);
+        return forged;
+    }
+    unreachable!()
+}
+
+pub fn challenge50() {
+    let forged = forge(ORIGINAL, PAYLOAD);
+    println!("{}", String::from_utf8_lossy(&forged));
+    println!("{} {}", tohex(&hash(ORIGINAL)), tohex(&hash(&forged)));
+}
+
+#[test]
+fn test() {
+    assert_eq!(tohex(&hash(ORIGINAL)), "296b8d7cb78a243dda4d0a61d33bbdd1");
+
+    let forged = forge(ORIGINAL, PAYLOAD);
+    assert!(forged.starts_with(PAYLOAD.as_bytes()));
+    assert_eq!(hash(&forged), hash(ORIGINAL));
+
+    // the only line break is the original one, at the end
+    assert_eq!(forged.iter().position(|b| *b == b'\n'), Some(forged.len() - 1));
+}
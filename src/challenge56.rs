@@ -0,0 +1,127 @@
+use aes::CipherError;
+use base64::decode;
+use bytes::random_bytes;
+use rc4::rc4;
+use std::env;
+
+// Trials per prefix length, as a power of two.  The challenge suggests 2^24;
+// 2^20 gets about a quarter of the cookie and takes a minute with --release.
+// The default only keeps `cargo run` quick - pass something bigger as the
+// second argument, e.g. `cargo run --release challenge56 24`.
+static DEFAULT_TRIALS_LOG2: u32 = 12;
+
+// The sixteenth and thirty-second keystream bytes are biased towards 240 and 224
+// (Z16 and Z32, counting from 1).
+static Z16: usize = 15;
+static Z16_BIAS: u8 = 240;
+static Z32: usize = 31;
+static Z32_BIAS: u8 = 224;
+
+fn cookie() -> Vec<u8> {
+    decode("QkUgU1VSRSBUTyBEUklOSyBZT1VSIE9WQUxUSU5F").unwrap()
+}
+
+// the request is sent with the cookie after it, under a fresh random key every
+// time
+fn oracle(request: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let mut cleartext = request.to_vec();
+    cleartext.extend(cookie());
+    rc4(&cleartext, &random_bytes(16))
+}
+
+// With a prefix of `prefix_len` bytes, the cookie byte at keystream position
+// `pos` is pos - prefix_len.  Counts how often each ciphertext byte turns up at
+// Z16 and Z32, then guesses each cookie byte as the one that would make the
+// commonest ciphertext byte the biased keystream byte.  Where both biases
+// reach a cookie byte their counts are added together.
+fn recover_cookie<F>(oracle_fn: F, trials: u64) -> Vec<u8>
+    where F: Fn(&[u8]) -> Result<Vec<u8>, CipherError>
+{
+    let cookie_len = oracle_fn(b"").unwrap().len();
+    let mut scores = vec![[0u64; 256]; cookie_len];
+
+    for prefix_len in 0..Z32 + 1 {
+        let reaches_cookie = |pos: usize| pos >= prefix_len && pos - prefix_len < cookie_len;
+        if !reaches_cookie(Z16) && !reaches_cookie(Z32) {
+            continue;
+        }
+        let prefix = vec![b'A'; prefix_len];
+        let mut z16_counts = [0u64; 256];
+        let mut z32_counts = [0u64; 256];
+        for _ in 0..trials {
+            let ciphertext = oracle_fn(&prefix).unwrap();
+            if ciphertext.len() > Z32 {
+                z32_counts[ciphertext[Z32] as usize] += 1;
+            }
+            if ciphertext.len() > Z16 {
+                z16_counts[ciphertext[Z16] as usize] += 1;
+            }
+        }
+
+        for &(pos, bias, ref counts) in
+            [(Z16, Z16_BIAS, z16_counts), (Z32, Z32_BIAS, z32_counts)].iter() {
+            if !reaches_cookie(pos) {
+                continue;
+            }
+            for c in 0..256 {
+                scores[pos - prefix_len][c ^ bias as usize] += counts[c];
+            }
+        }
+    }
+
+    scores
+        .iter()
+        .map(|score| (0..256).max_by_key(|b| score[*b]).unwrap() as u8)
+        .collect()
+}
+
+pub fn challenge56() {
+    let trials_log2 = env::args()
+        .nth(2)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_TRIALS_LOG2);
+    let recovered = recover_cookie(oracle, 1 << trials_log2);
+    let correct = recovered
+        .iter()
+        .zip(cookie().iter())
+        .filter(|&(a, b)| a == b)
+        .count();
+    println!("2^{} trials: {:?}", trials_log2, String::from_utf8_lossy(&recovered));
+    println!("{} of {} bytes right", correct, recovered.len());
+}
+
+// An oracle whose keystream always has the biased bytes where they should be,
+// to check the bookkeeping without millions of trials.
+#[cfg(test)]
+fn perfectly_biased_oracle(request: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let mut ciphertext = try!(oracle(request));
+    let mut cleartext = request.to_vec();
+    cleartext.extend(cookie());
+    for &(pos, bias) in [(Z16, Z16_BIAS), (Z32, Z32_BIAS)].iter() {
+        if pos < cleartext.len() {
+            ciphertext[pos] = cleartext[pos] ^ bias;
+        }
+    }
+    Ok(ciphertext)
+}
+
+#[test]
+fn test() {
+    assert_eq!(recover_cookie(perfectly_biased_oracle, 4), cookie());
+}
+
+// The real thing.  The biases are small - over 2^24 keys Z16 is 240 about
+// 3.7% more often than any other value and Z32 is 224 about 2.4% more often -
+// so even a one byte cookie needs 2^23 trials to come out many standard
+// deviations clear of the other 255 guesses.  That's too slow for a debug
+// build: `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn test_real_oracle() {
+    let short_oracle = |request: &[u8]| {
+        let mut cleartext = request.to_vec();
+        cleartext.push(b'H');
+        rc4(&cleartext, &random_bytes(16))
+    };
+    assert_eq!(recover_cookie(short_oracle, 1 << 23), b"H");
+}
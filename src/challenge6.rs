@@ -1,62 +1,64 @@
 use bytes::*;
 use std::cmp::Ordering;
 use base64::{decode_config, MIME};
+use keylength::key_lengths;
+use scoring::{ChiSquared, Scorer};
 
-pub fn transpose(data: &[u8], keysize: usize) -> Vec<Vec<u8>> {
-    // create our vec of vecs
-    let mut transposed = Vec::new();
-    for _ in 0..keysize {
-        transposed.push(Vec::new());
-    }
-
-    // go through each chunk and add the byte to the appropriate vec in
-    // transposed
-    for c in data.chunks(keysize) {
-        for (i, b) in c.iter().enumerate() {
-            transposed[i].push(*b);
-        }
-    }
-    transposed
-}
-
-pub fn challenge6() {
-    let ciphertext = decode_config(include_str!("challenge-6.dat"), MIME).unwrap();
-
+// Guesses the key of a repeating key xor.  Each of the four likeliest key
+// sizes by keylength::key_lengths is tried, solving every column of the
+// ciphertext as a single byte xor rated by `scorer`, and the key size whose
+// whole decryption `whole` rates best wins - `whole` can be a scorer that
+// needs the cleartext in order, like formats::Entropy or MagicBytes.  A
+// column's best key is passed over for a runner up if it leaves control
+// characters in the cleartext.
+pub fn break_repeating_xor<S, W>(ciphertext: &[u8], scorer: &S, whole: &W) -> Option<Vec<u8>>
+    where S: Scorer + ?Sized,
+          W: Scorer + ?Sized
+{
     // find key size
-    let mut answers = (2..40)
-        .map(|keysize| {
-            let d = hamming_distance(&ciphertext[0..keysize], &ciphertext[keysize..keysize * 2]) +
-                    hamming_distance(&ciphertext[0..keysize],
-                                     &ciphertext[keysize * 2..keysize * 3]) +
-                    hamming_distance(&ciphertext[0..keysize],
-                                     &ciphertext[keysize * 3..keysize * 4]);
-
-            (keysize, (d * 100) / keysize as u32)
-        })
-        .collect::<Vec<_>>();
-    answers.sort_by_key(|a| a.1);
-    let likely_key_sizes = answers.iter().map(|a| a.0).take(4);
+    let likely_key_sizes = key_lengths(ciphertext, 2..40).into_iter().map(|l| l.0).take(4);
 
     // for likely key sizes, transpose matrix (so that each block was xored with
     // the same byte)
     let mut keys_and_ratings = Vec::new();
     for k in likely_key_sizes {
         let mut key = Vec::new();
-        let mut rating = 0f32;
         for ciphertext in transpose(&ciphertext, k) {
-            let (k, block_rating, _) = most_english_xor(&ciphertext).unwrap();
+            let (k, _, _) = most_likely_text_xor(&ciphertext, scorer, 4).unwrap();
             key.push(k);
-            rating = rating + block_rating;
         }
-        keys_and_ratings.push((key, rating / k as f32));
+        let rating = whole.score(&repeat_xor(ciphertext, &key));
+        keys_and_ratings.push((key, rating));
     }
     keys_and_ratings.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap_or(Ordering::Less));
+    if keys_and_ratings.is_empty() {
+        None
+    } else {
+        Some(keys_and_ratings.swap_remove(0).0)
+    }
+}
 
-    let ref key = keys_and_ratings[0].0;
-    let keystr = String::from_utf8_lossy(&key);
-    let rating = keys_and_ratings[0].1;
-    println!("{} {} {}", keystr, rating, key.len());
+pub fn challenge6() {
+    let ciphertext = decode_config(include_str!("challenge-6.dat"), MIME).unwrap();
+    let english = ChiSquared::english();
+    let key = break_repeating_xor(&ciphertext, &english, &english).unwrap();
+    println!("{} {}", String::from_utf8_lossy(&key), key.len());
 
     println!("{}",
-             String::from_utf8(repeat_xor(&ciphertext, key)).unwrap());
+             String::from_utf8(repeat_xor(&ciphertext, &key)).unwrap());
+}
+
+#[test]
+fn test_break_repeating_xor() {
+    use formats::Entropy;
+
+    let ciphertext = decode_config(include_str!("challenge-6.dat"), MIME).unwrap();
+    let english = ChiSquared::english();
+    for whole in [&english as &Scorer, &Entropy].iter() {
+        assert_eq!(break_repeating_xor(&ciphertext, &english, *whole).unwrap(),
+                   b"Terminator X: Bring the noise");
+    }
+
+    // too short for two blocks of any key size
+    assert_eq!(break_repeating_xor(b"abc", &english, &english), None);
 }
@@ -0,0 +1,257 @@
+// DES (FIPS 46-3) and Triple DES (SP 800-67), mostly to have a real cipher
+// with 8 byte blocks to point the block size discovering attacks at.  Written
+// straight from the standard's tables, so bit 1 is the most significant bit
+// everywhere.  The key's parity bits are ignored.
+
+use blockcipher::{BlockCipher, CipherError};
+
+pub const BLOCK_SIZE: usize = 8;
+
+static IP: [u8; 64] = [58, 50, 42, 34, 26, 18, 10, 2, 60, 52, 44, 36, 28, 20, 12, 4, 62, 54, 46,
+                       38, 30, 22, 14, 6, 64, 56, 48, 40, 32, 24, 16, 8, 57, 49, 41, 33, 25, 17,
+                       9, 1, 59, 51, 43, 35, 27, 19, 11, 3, 61, 53, 45, 37, 29, 21, 13, 5, 63,
+                       55, 47, 39, 31, 23, 15, 7];
+
+static FP: [u8; 64] = [40, 8, 48, 16, 56, 24, 64, 32, 39, 7, 47, 15, 55, 23, 63, 31, 38, 6, 46,
+                       14, 54, 22, 62, 30, 37, 5, 45, 13, 53, 21, 61, 29, 36, 4, 44, 12, 52, 20,
+                       60, 28, 35, 3, 43, 11, 51, 19, 59, 27, 34, 2, 42, 10, 50, 18, 58, 26, 33,
+                       1, 41, 9, 49, 17, 57, 25];
+
+// expands the 32 bit half block to 48 bits
+static E: [u8; 48] = [32, 1, 2, 3, 4, 5, 4, 5, 6, 7, 8, 9, 8, 9, 10, 11, 12, 13, 12, 13, 14, 15,
+                      16, 17, 16, 17, 18, 19, 20, 21, 20, 21, 22, 23, 24, 25, 24, 25, 26, 27, 28,
+                      29, 28, 29, 30, 31, 32, 1];
+
+static P: [u8; 32] = [16, 7, 20, 21, 29, 12, 28, 17, 1, 15, 23, 26, 5, 18, 31, 10, 2, 8, 24, 14,
+                      32, 27, 3, 9, 19, 13, 30, 6, 22, 11, 4, 25];
+
+// drops the parity bits, leaving 56
+static PC1: [u8; 56] = [57, 49, 41, 33, 25, 17, 9, 1, 58, 50, 42, 34, 26, 18, 10, 2, 59, 51, 43,
+                        35, 27, 19, 11, 3, 60, 52, 44, 36, 63, 55, 47, 39, 31, 23, 15, 7, 62, 54,
+                        46, 38, 30, 22, 14, 6, 61, 53, 45, 37, 29, 21, 13, 5, 28, 20, 12, 4];
+
+// picks 48 of the 56 key bits for each round
+static PC2: [u8; 48] = [14, 17, 11, 24, 1, 5, 3, 28, 15, 6, 21, 10, 23, 19, 12, 4, 26, 8, 16, 7,
+                        27, 20, 13, 2, 41, 52, 31, 37, 47, 55, 30, 40, 51, 45, 33, 48, 44, 49,
+                        39, 56, 34, 53, 46, 42, 50, 36, 29, 32];
+
+static SHIFTS: [u32; 16] = [1, 1, 2, 2, 2, 2, 2, 2, 1, 2, 2, 2, 2, 2, 2, 1];
+
+static SBOXES: [[u8; 64]; 8] =
+    [[14, 4, 13, 1, 2, 15, 11, 8, 3, 10, 6, 12, 5, 9, 0, 7, 0, 15, 7, 4, 14, 2, 13, 1, 10, 6,
+      12, 11, 9, 5, 3, 8, 4, 1, 14, 8, 13, 6, 2, 11, 15, 12, 9, 7, 3, 10, 5, 0, 15, 12, 8, 2, 4,
+      9, 1, 7, 5, 11, 3, 14, 10, 0, 6, 13],
+     [15, 1, 8, 14, 6, 11, 3, 4, 9, 7, 2, 13, 12, 0, 5, 10, 3, 13, 4, 7, 15, 2, 8, 14, 12, 0, 1,
+      10, 6, 9, 11, 5, 0, 14, 7, 11, 10, 4, 13, 1, 5, 8, 12, 6, 9, 3, 2, 15, 13, 8, 10, 1, 3, 15,
+      4, 2, 11, 6, 7, 12, 0, 5, 14, 9],
+     [10, 0, 9, 14, 6, 3, 15, 5, 1, 13, 12, 7, 11, 4, 2, 8, 13, 7, 0, 9, 3, 4, 6, 10, 2, 8, 5,
+      14, 12, 11, 15, 1, 13, 6, 4, 9, 8, 15, 3, 0, 11, 1, 2, 12, 5, 10, 14, 7, 1, 10, 13, 0, 6,
+      9, 8, 7, 4, 15, 14, 3, 11, 5, 2, 12],
+     [7, 13, 14, 3, 0, 6, 9, 10, 1, 2, 8, 5, 11, 12, 4, 15, 13, 8, 11, 5, 6, 15, 0, 3, 4, 7, 2,
+      12, 1, 10, 14, 9, 10, 6, 9, 0, 12, 11, 7, 13, 15, 1, 3, 14, 5, 2, 8, 4, 3, 15, 0, 6, 10, 1,
+      13, 8, 9, 4, 5, 11, 12, 7, 2, 14],
+     [2, 12, 4, 1, 7, 10, 11, 6, 8, 5, 3, 15, 13, 0, 14, 9, 14, 11, 2, 12, 4, 7, 13, 1, 5, 0, 15,
+      10, 3, 9, 8, 6, 4, 2, 1, 11, 10, 13, 7, 8, 15, 9, 12, 5, 6, 3, 0, 14, 11, 8, 12, 7, 1, 14,
+      2, 13, 6, 15, 0, 9, 10, 4, 5, 3],
+     [12, 1, 10, 15, 9, 2, 6, 8, 0, 13, 3, 4, 14, 7, 5, 11, 10, 15, 4, 2, 7, 12, 9, 5, 6, 1, 13,
+      14, 0, 11, 3, 8, 9, 14, 15, 5, 2, 8, 12, 3, 7, 0, 4, 10, 1, 13, 11, 6, 4, 3, 2, 12, 9, 5,
+      15, 10, 11, 14, 1, 7, 6, 0, 8, 13],
+     [4, 11, 2, 14, 15, 0, 8, 13, 3, 12, 9, 7, 5, 10, 6, 1, 13, 0, 11, 7, 4, 9, 1, 10, 14, 3, 5,
+      12, 2, 15, 8, 6, 1, 4, 11, 13, 12, 3, 7, 14, 10, 15, 6, 8, 0, 5, 9, 2, 6, 11, 13, 8, 1, 4,
+      10, 7, 9, 5, 0, 15, 14, 2, 3, 12],
+     [13, 2, 8, 4, 6, 15, 11, 1, 10, 9, 3, 14, 5, 0, 12, 7, 1, 15, 13, 8, 10, 3, 7, 4, 12, 5, 6,
+      11, 0, 14, 9, 2, 7, 11, 4, 1, 9, 12, 14, 2, 0, 6, 10, 13, 15, 3, 5, 8, 2, 1, 14, 7, 4, 10,
+      8, 13, 15, 12, 9, 0, 3, 5, 6, 11]];
+
+// Output bit i is input bit table[i], where the input is `input_bits` wide.
+fn permute(input: u64, input_bits: u32, table: &[u8]) -> u64 {
+    table
+        .iter()
+        .fold(0u64,
+              |acc, pos| (acc << 1) | ((input >> (input_bits - *pos as u32)) & 1))
+}
+
+fn to_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn from_u64(val: u64, bytes: &mut [u8]) {
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (val >> (8 * (7 - i))) as u8;
+    }
+}
+
+// the round function: expand, add the subkey, S-boxes, permute
+fn feistel(half: u32, subkey: u64) -> u32 {
+    let expanded = permute(half as u64, 32, &E) ^ subkey;
+    let mut substituted = 0u32;
+    for (i, sbox) in SBOXES.iter().enumerate() {
+        let six_bits = ((expanded >> (42 - 6 * i)) & 0x3f) as usize;
+        // the outer two bits pick the row, the middle four the column
+        let row = ((six_bits & 0x20) >> 4) | (six_bits & 1);
+        let col = (six_bits >> 1) & 0xf;
+        substituted = (substituted << 4) | sbox[row * 16 + col] as u32;
+    }
+    permute(substituted as u64, 32, &P) as u32
+}
+
+pub struct Des {
+    subkeys: [u64; 16],
+}
+
+impl Des {
+    pub fn new(key: &[u8]) -> Result<Des, CipherError> {
+        if key.len() != 8 {
+            return Err(CipherError::InvalidKeyLength);
+        }
+
+        // rotate each 28 bit half of the key, then pick out 48 bits
+        let permuted = permute(to_u64(key), 64, &PC1);
+        let mut c = (permuted >> 28) as u32;
+        let mut d = (permuted & 0x0fffffff) as u32;
+        let mut subkeys = [0u64; 16];
+        for (subkey, shift) in subkeys.iter_mut().zip(SHIFTS.iter()) {
+            c = ((c << shift) | (c >> (28 - shift))) & 0x0fffffff;
+            d = ((d << shift) | (d >> (28 - shift))) & 0x0fffffff;
+            *subkey = permute(((c as u64) << 28) | d as u64, 56, &PC2);
+        }
+        Ok(Des { subkeys: subkeys })
+    }
+
+    fn crypt<'a, I>(&self, block: &mut [u8], subkeys: I)
+        where I: Iterator<Item = &'a u64>
+    {
+        assert_eq!(block.len(), BLOCK_SIZE);
+        let permuted = permute(to_u64(block), 64, &IP);
+        let mut left = (permuted >> 32) as u32;
+        let mut right = permuted as u32;
+        for subkey in subkeys {
+            let next_right = left ^ feistel(right, *subkey);
+            left = right;
+            right = next_right;
+        }
+        // the halves aren't swapped after the last round
+        let preoutput = ((right as u64) << 32) | left as u64;
+        from_u64(permute(preoutput, 64, &FP), block);
+    }
+}
+
+impl BlockCipher for Des {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        self.crypt(block, self.subkeys.iter());
+    }
+
+    // the same thing with the subkeys backwards
+    fn decrypt_block(&self, block: &mut [u8]) {
+        self.crypt(block, self.subkeys.iter().rev());
+    }
+}
+
+// Encrypt-decrypt-encrypt with three DES keys.  A 16 byte key is two-key
+// 3DES (the third key is the first again); 24 bytes is three-key.  With all
+// three keys the same it's just DES.
+pub struct TripleDes {
+    keys: [Des; 3],
+}
+
+impl TripleDes {
+    pub fn new(key: &[u8]) -> Result<TripleDes, CipherError> {
+        let k3 = match key.len() {
+            16 => &key[0..8],
+            24 => &key[16..24],
+            _ => return Err(CipherError::InvalidKeyLength),
+        };
+        Ok(TripleDes {
+               keys: [try!(Des::new(&key[0..8])), try!(Des::new(&key[8..16])), try!(Des::new(k3))],
+           })
+    }
+}
+
+impl BlockCipher for TripleDes {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        self.keys[0].encrypt_block(block);
+        self.keys[1].decrypt_block(block);
+        self.keys[2].encrypt_block(block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        self.keys[2].decrypt_block(block);
+        self.keys[1].encrypt_block(block);
+        self.keys[0].decrypt_block(block);
+    }
+}
+
+#[cfg(test)]
+fn check_des<C: BlockCipher>(cipher: &C, cleartext: &str, ciphertext: &str) {
+    use aes::{decrypt_ecb_with, encrypt_ecb_with};
+    use hexstring::{fromhex, tohex};
+
+    let encrypted = encrypt_ecb_with(cipher, &fromhex(cleartext).unwrap()).unwrap();
+    assert_eq!(tohex(&encrypted), ciphertext);
+    assert_eq!(tohex(&decrypt_ecb_with(cipher, &encrypted).unwrap()), cleartext);
+}
+
+#[test]
+fn test_des() {
+    use hexstring::{fromhex, tohex};
+
+    // the tables are each other's inverses
+    for i in 0..64 {
+        assert_eq!(FP[IP[i] as usize - 1] as usize, i + 1);
+    }
+
+    check_des(&Des::new(&fromhex("133457799bbcdff1").unwrap()).unwrap(),
+              "0123456789abcdef",
+              "85e813540f0ab405");
+    check_des(&Des::new(&fromhex("0123456789abcdef").unwrap()).unwrap(),
+              &tohex(b"Now is the time for all "),
+              "3fa40e8a984d48156a271787ab8883f9893d51ec4b563b53");
+    // from the NBS validation suite - the subkeys are all zero
+    check_des(&Des::new(&fromhex("0101010101010101").unwrap()).unwrap(),
+              "95f8a5e5dd31d900",
+              "8000000000000000");
+    assert!(Des::new(&[0u8; 7]).is_err());
+}
+
+#[test]
+fn test_triple_des() {
+    use hexstring::{fromhex, tohex};
+
+    // SP 800-67 appendix B, typo and all
+    let key = fromhex("0123456789abcdef23456789abcdef01456789abcdef0123").unwrap();
+    check_des(&TripleDes::new(&key).unwrap(),
+              &tohex(b"The qufck brown fox jump"),
+              "a826fd8ce53b855fcce21c8112256fe668d5c05dd9b6b900");
+
+    // keying option 2, where K3 is K1 - the usual two-key known answer, which
+    // OpenSSL's des-ede agrees with
+    check_des(&TripleDes::new(&fromhex("0123456789abcdeffedcba9876543210").unwrap()).unwrap(),
+              "0123456789abcde7",
+              "7f1d0a77826b8aff");
+
+    // two-key is three-key with K1 repeated, and the DES special case
+    let cipher = TripleDes::new(&key[..16]).unwrap();
+    let mut ede3_key = key[..16].to_vec();
+    ede3_key.extend_from_slice(&key[..8]);
+    let mut a = [1u8; 8];
+    let mut b = [1u8; 8];
+    cipher.encrypt_block(&mut a);
+    TripleDes::new(&ede3_key).unwrap().encrypt_block(&mut b);
+    assert_eq!(a, b);
+
+    let mut same_key = key[..8].to_vec();
+    same_key.extend_from_slice(&key[..8]);
+    let mut a = [2u8; 8];
+    let mut b = [2u8; 8];
+    TripleDes::new(&same_key).unwrap().encrypt_block(&mut a);
+    Des::new(&key[..8]).unwrap().encrypt_block(&mut b);
+    assert_eq!(a, b);
+}
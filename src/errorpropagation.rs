@@ -0,0 +1,92 @@
+use aes::{decrypt_cbc, encrypt_cbc, decrypt_cfb128, encrypt_cfb128, decrypt_cfb8, encrypt_cfb8,
+          decrypt_ctr, encrypt_ctr, decrypt_ecb, encrypt_ecb, decrypt_ofb, encrypt_ofb,
+          decrypt_pcbc, encrypt_pcbc, CipherError};
+
+// Not one of the challenges - flip a single ciphertext bit under each mode, in
+// the same way as challenge 16, and show what it does to the cleartext.
+
+static KEY: [u8; 16] = [1; 16];
+static IV: [u8; 16] = [0; 16];
+static NONCE: u64 = 0;
+
+static CLEARTEXT: &'static [u8; 64] = b"comment1=cooking%20MCs;userdata=\
+                                        ;comment2=%20like%20a%20pound%20";
+
+// the byte whose low bit gets flipped - the start of the second block
+static FLIPPED: usize = 16;
+
+type Mode = fn(&[u8], &[u8], &[u8]) -> Result<Vec<u8>, CipherError>;
+
+fn encrypt_ecb_mode(cleartext: &[u8], key: &[u8], _: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_ecb(cleartext, key)
+}
+
+fn decrypt_ecb_mode(ciphertext: &[u8], key: &[u8], _: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_ecb(ciphertext, key)
+}
+
+fn encrypt_ctr_mode(cleartext: &[u8], key: &[u8], _: &[u8]) -> Result<Vec<u8>, CipherError> {
+    encrypt_ctr(cleartext, key, &NONCE)
+}
+
+fn decrypt_ctr_mode(ciphertext: &[u8], key: &[u8], _: &[u8]) -> Result<Vec<u8>, CipherError> {
+    decrypt_ctr(ciphertext, key, &NONCE)
+}
+
+fn modes() -> Vec<(&'static str, Mode, Mode)> {
+    vec![("ECB", encrypt_ecb_mode, decrypt_ecb_mode),
+         ("CBC", encrypt_cbc, decrypt_cbc),
+         ("PCBC", encrypt_pcbc, decrypt_pcbc),
+         ("CFB8", encrypt_cfb8, decrypt_cfb8),
+         ("CFB128", encrypt_cfb128, decrypt_cfb128),
+         ("OFB", encrypt_ofb, decrypt_ofb),
+         ("CTR", encrypt_ctr_mode, decrypt_ctr_mode)]
+}
+
+// Encrypt, flip the bit, decrypt and mark each cleartext byte: '.' if it's
+// untouched, '^' if exactly the same bit flipped (so an attacker controls
+// it), '#' if it's garbage.
+fn damage(encrypt: Mode, decrypt: Mode) -> String {
+    let mut ciphertext = encrypt(CLEARTEXT, &KEY, &IV).unwrap();
+    ciphertext[FLIPPED] ^= 1;
+    let decrypted = decrypt(&ciphertext, &KEY, &IV).unwrap();
+
+    CLEARTEXT
+        .iter()
+        .zip(decrypted.iter())
+        .map(|(a, b)| match a ^ b {
+                 0 => '.',
+                 1 => '^',
+                 _ => '#',
+             })
+        .collect()
+}
+
+pub fn errorpropagation() {
+    println!("flipping bit 0 of ciphertext byte {}", FLIPPED);
+    println!("{:>8}  {}", "", String::from_utf8_lossy(CLEARTEXT));
+    for (name, encrypt, decrypt) in modes() {
+        println!("{:>8}  {}", name, damage(encrypt, decrypt));
+    }
+}
+
+#[test]
+fn test() {
+    // '#' is anything - a garbled byte can come out right by chance
+    let expected = ["................################................................",
+                    "................################^...............................",
+                    "................################################################",
+                    "................^################...............................",
+                    "................^...............################................",
+                    "................^...............................................",
+                    "................^..............................................."];
+
+    for ((name, encrypt, decrypt), pattern) in modes().into_iter().zip(expected.iter()) {
+        let damage = damage(encrypt, decrypt);
+        assert_eq!(damage.len(), pattern.len(), "{}", name);
+        for (d, p) in damage.chars().zip(pattern.chars()) {
+            assert!(p == '#' || d == p, "{}: {}", name, damage);
+        }
+        assert_eq!(damage.matches('#').count() > 0, pattern.contains('#'), "{}", name);
+    }
+}
@@ -0,0 +1,122 @@
+use base64::decode;
+use blockcipher::CipherError;
+use bytes::{inplace_xor, ranked_xors};
+use keystream::{keystream, Keystream};
+use scoring::{ChiSquared, NgramModel, Scorer};
+use std::f32;
+use std::fs::File;
+use std::io::{self, Read};
+
+// Attacks on stream ciphers that reuse a nonce (or, for RC4, a key), so that
+// every message is xored with the start of the same keystream.  Nothing here
+// depends on the cipher - only the ciphertexts are needed.
+
+// Encrypts every cleartext with a fresh generator from `new_generator`, so
+// they all share one keystream.
+pub fn encrypt_fixed_nonce<K, F>(cleartexts: &[Vec<u8>],
+                                 new_generator: F)
+                                 -> Result<Vec<Vec<u8>>, CipherError>
+    where K: Keystream,
+          F: Fn() -> K
+{
+    let mut ciphertexts = Vec::new();
+    for cleartext in cleartexts {
+        let stream = try!(keystream(&mut new_generator(), cleartext.len()));
+        ciphertexts.push(inplace_xor(stream, cleartext));
+    }
+    Ok(ciphertexts)
+}
+
+// how many of each column's best keys are weighed against the column before
+const CANDIDATES: usize = 10;
+
+// Recovers as much of the keystream as the longest ciphertext covers.  Byte i
+// of every ciphertext long enough to have one was xored with the same
+// keystream byte, so each column is a single byte xor to be scored like
+// challenge 3.  Columns only a few ciphertexts reach are mostly guesswork.
+pub fn break_fixed_nonce(ciphertexts: &[Vec<u8>]) -> Vec<u8> {
+    break_fixed_nonce_with(ciphertexts, &ChiSquared::english(), &NgramModel::english(2))
+}
+
+// break_fixed_nonce, rating the columns with any scorer.  The best rated key
+// for a column isn't always taken: of the few best, the one that leaves the
+// rows' cleartexts so far rating highest on average by `whole` wins, so a key
+// that rates well alone but makes nonsense of the rows gives way to a runner
+// up.  `whole` sees each row from its start, so it can be a scorer that needs
+// the cleartext in order; with a bigram model only the newest pair of each row
+// makes a difference.
+pub fn break_fixed_nonce_with<S, W>(ciphertexts: &[Vec<u8>], scorer: &S, whole: &W) -> Vec<u8>
+    where S: Scorer + ?Sized,
+          W: Scorer + ?Sized
+{
+    let max_len = ciphertexts.iter().map(|c| c.len()).max().unwrap_or(0);
+    let mut cleartexts = vec![Vec::with_capacity(max_len); ciphertexts.len()];
+    let mut keystream = Vec::with_capacity(max_len);
+    for i in 0..max_len {
+        let rows = (0..ciphertexts.len())
+            .filter(|&r| i < ciphertexts[r].len())
+            .collect::<Vec<_>>();
+        let column = rows.iter().map(|&r| ciphertexts[r][i]).collect::<Vec<_>>();
+        let candidates = ranked_xors(&column, scorer, CANDIDATES);
+
+        // ties go to the better rated column, as the first column's will when
+        // `whole` needs more than one byte
+        let mut best = &candidates[0];
+        let mut best_fit = f32::NEG_INFINITY;
+        for candidate in &candidates {
+            let mut fit = 0f32;
+            for (&r, &b) in rows.iter().zip(&candidate.2) {
+                cleartexts[r].push(b);
+                fit += whole.score(&cleartexts[r]);
+                cleartexts[r].pop();
+            }
+            let fit = fit / rows.len() as f32;
+            if fit > best_fit {
+                best = candidate;
+                best_fit = fit;
+            }
+        }
+
+        for (&r, &b) in rows.iter().zip(&best.2) {
+            cleartexts[r].push(b);
+        }
+        keystream.push(best.0);
+    }
+    keystream
+}
+
+// Ciphertexts from a file, one base64 string per line like challenge-20.dat.
+// Blank lines are skipped.
+pub fn load_ciphertexts(path: &str) -> io::Result<Vec<Vec<u8>>> {
+    let mut contents = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut contents));
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| decode(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+        .collect()
+}
+
+#[test]
+fn test_break_fixed_nonce() {
+    use bytes::random_bytes;
+    use chacha20::ChaCha20;
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/challenge-20.dat");
+    let cleartexts = load_ciphertexts(path).unwrap();
+    assert_eq!(cleartexts.len(), 60);
+
+    let key = random_bytes(32);
+    let ciphertexts = encrypt_fixed_nonce(&cleartexts, || ChaCha20::new(&key, &[0; 12], 0).unwrap())
+        .unwrap();
+    let expected = keystream(&mut ChaCha20::new(&key, &[0; 12], 0).unwrap(), 200).unwrap();
+    let recovered = break_fixed_nonce(&ciphertexts);
+    assert_eq!(recovered.len(), ciphertexts.iter().map(|c| c.len()).max().unwrap());
+
+    // every ciphertext reaches the first 53 bytes, and with 60 samples a column
+    // is hardly ever wrong
+    let right = (0..53).filter(|&i| recovered[i] == expected[i]).count();
+    assert!(right >= 50, "only {} of 53 keystream bytes right", right);
+}
+
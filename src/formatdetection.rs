@@ -0,0 +1,40 @@
+use bytes::{most_likely_xor, rand_u8, random_bytes, xor1};
+use formats::{identify, Json, MagicBytes, Utf16};
+use scoring::Scorer;
+
+// Not one of the challenges - the xor solvers again, on cleartexts that
+// aren't English, using the format scorers instead.
+
+fn break_single_byte_xor<S: Scorer>(description: &str, cleartext: &[u8], scorer: &S) {
+    let ciphertext = xor1(cleartext, rand_u8());
+    let (key, _, recovered) = most_likely_xor(&ciphertext, scorer).unwrap();
+    println!("{}: key {}, looks like {}, {}",
+             description,
+             key,
+             identify(&recovered).unwrap_or("nothing much"),
+             if recovered == cleartext { "right" } else { "wrong" });
+}
+
+pub fn formatdetection() {
+    let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+    png.extend(random_bytes(200));
+    break_single_byte_xor("PNG", &png, &MagicBytes);
+
+    let json = br#"{"id": 17, "tags": ["alpha", "beta"], "active": true, "ratio": 0.25}"#;
+    break_single_byte_xor("JSON", json, &Json);
+
+    let utf16: Vec<u8> = "Ciphertexts aren't always ASCII"
+        .encode_utf16()
+        .flat_map(|u| vec![u as u8, (u >> 8) as u8])
+        .collect();
+    break_single_byte_xor("UTF-16", &utf16, &Utf16);
+
+    // Base64 can't be broken this way: flipping 0x20 swaps the case of every
+    // letter, and other keys swap digits and letters about, so plenty of wrong
+    // keys leave it in the alphabet.  It can still be recognised once decrypted.
+    let base64 = include_bytes!("challenge-20.dat");
+    let first_line = base64.split(|b| *b == b'\n').next().unwrap();
+    println!("{}: looks like {}",
+             String::from_utf8_lossy(&first_line[..20]),
+             identify(first_line).unwrap_or("nothing much"));
+}
@@ -0,0 +1,489 @@
+use scoring::Scorer;
+use std::char::decode_utf16;
+use std::str;
+
+// Scorers for cleartexts that aren't prose - file headers, JSON, UTF-16,
+// base64 - and a guess at which of those some bytes are.  Each gives a score
+// from 0 to 1, except Entropy.
+//
+// They only work on the kind of candidate they describe: magic bytes and JSON
+// need the start of the real thing, not a column of every nth byte the way
+// challenge 6 and 20 slice their ciphertexts.  Entropy and base64 don't care
+// about order.
+
+static SIGNATURES: &'static [(&'static str, &'static [u8])] =
+    &[("PNG", b"\x89PNG\r\n\x1a\n"),
+      ("ZIP", b"PK\x03\x04"),
+      ("PDF", b"%PDF-"),
+      ("GIF", b"GIF8"),
+      ("JPEG", b"\xff\xd8\xff"),
+      ("gzip", b"\x1f\x8b\x08"),
+      ("ELF", b"\x7fELF"),
+      ("XML", b"<?xml"),
+      ("UTF-8 BOM", b"\xef\xbb\xbf"),
+      ("UTF-16LE BOM", b"\xff\xfe"),
+      ("UTF-16BE BOM", b"\xfe\xff")];
+
+// Known file signatures at the start of the data.  Scores the fraction of the
+// best matching signature that's there, so a ciphertext too short for the
+// whole signature still gets a sensible score.
+pub struct MagicBytes;
+
+impl MagicBytes {
+    pub fn detect(data: &[u8]) -> Option<&'static str> {
+        SIGNATURES
+            .iter()
+            .find(|&&(_, signature)| data.starts_with(signature))
+            .map(|&(name, _)| name)
+    }
+}
+
+impl Scorer for MagicBytes {
+    fn score(&self, text: &[u8]) -> f32 {
+        SIGNATURES
+            .iter()
+            .map(|&(_, signature)| {
+                     let matching = signature
+                         .iter()
+                         .zip(text)
+                         .take_while(|&(a, b)| a == b)
+                         .count();
+                     matching as f32 / signature.len().min(text.len()).max(1) as f32
+                 })
+            .fold(0.0, f32::max)
+    }
+}
+
+// Why JSON parsing stopped - running out of data isn't a failure, since the
+// ciphertext may only be the start of a document.
+enum Stop {
+    End,
+    Invalid,
+}
+
+struct JsonParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+static MAX_DEPTH: usize = 128;
+
+impl<'a> JsonParser<'a> {
+    fn peek(&self) -> Result<u8, Stop> {
+        self.data.get(self.pos).cloned().ok_or(Stop::End)
+    }
+
+    fn next(&mut self) -> Result<u8, Stop> {
+        let b = try!(self.peek());
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), Stop> {
+        if try!(self.peek()) != expected {
+            return Err(Stop::Invalid);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.data.len() && b" \t\n\r".contains(&self.data[self.pos]) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<(), Stop> {
+        self.skip_whitespace();
+        match try!(self.peek()) {
+            b'{' => self.container(b'}', true),
+            b'[' => self.container(b']', false),
+            b'"' => self.string(),
+            b'-' | b'0'...b'9' => self.number(),
+            b't' => self.literal(b"true"),
+            b'f' => self.literal(b"false"),
+            b'n' => self.literal(b"null"),
+            _ => Err(Stop::Invalid),
+        }
+    }
+
+    // an object (with keys) or an array
+    fn container(&mut self, close: u8, keys: bool) -> Result<(), Stop> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(Stop::Invalid);
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        if try!(self.peek()) == close {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(());
+        }
+        loop {
+            if keys {
+                self.skip_whitespace();
+                try!(self.string());
+                self.skip_whitespace();
+                try!(self.expect(b':'));
+            }
+            try!(self.value());
+            self.skip_whitespace();
+            match try!(self.next()) {
+                b',' => continue,
+                b if b == close => break,
+                _ => return Err(Stop::Invalid),
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<(), Stop> {
+        try!(self.expect(b'"'));
+        loop {
+            match try!(self.next()) {
+                b'"' => return Ok(()),
+                b'\\' => {
+                    match try!(self.next()) {
+                        b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {}
+                        b'u' => {
+                            for _ in 0..4 {
+                                if !(try!(self.next()) as char).is_digit(16) {
+                                    return Err(Stop::Invalid);
+                                }
+                            }
+                        }
+                        _ => return Err(Stop::Invalid),
+                    }
+                }
+                b if b < 0x20 => return Err(Stop::Invalid),
+                _ => {}
+            }
+        }
+    }
+
+    // Numbers can end the data, so the optional parts look ahead without
+    // treating the end as running out.
+    fn lookahead(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    fn digits(&mut self) -> Result<(), Stop> {
+        if !(try!(self.next()) as char).is_digit(10) {
+            return Err(Stop::Invalid);
+        }
+        while let Some(b'0'...b'9') = self.lookahead() {
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<(), Stop> {
+        if try!(self.peek()) == b'-' {
+            self.pos += 1;
+        }
+        if try!(self.peek()) == b'0' {
+            self.pos += 1;
+        } else {
+            try!(self.digits());
+        }
+        if self.lookahead() == Some(b'.') {
+            self.pos += 1;
+            try!(self.digits());
+        }
+        if let Some(b'e') | Some(b'E') = self.lookahead() {
+            self.pos += 1;
+            if let b'+' | b'-' = try!(self.peek()) {
+                self.pos += 1;
+            }
+            try!(self.digits());
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Result<(), Stop> {
+        for b in literal {
+            try!(self.expect(*b));
+        }
+        Ok(())
+    }
+}
+
+// How much of the text is valid JSON, as a fraction.  A document cut off part
+// way through counts as valid.
+pub struct Json;
+
+impl Json {
+    // a whole, valid document and nothing but whitespace after it
+    pub fn is_valid(text: &[u8]) -> bool {
+        let mut parser = JsonParser {
+            data: text,
+            pos: 0,
+            depth: 0,
+        };
+        if parser.value().is_err() {
+            return false;
+        }
+        parser.skip_whitespace();
+        parser.pos == text.len()
+    }
+}
+
+impl Scorer for Json {
+    fn score(&self, text: &[u8]) -> f32 {
+        if text.is_empty() {
+            return 0.0;
+        }
+        let mut parser = JsonParser {
+            data: text,
+            pos: 0,
+            depth: 0,
+        };
+        let valid = match parser.value() {
+            Err(Stop::Invalid) => parser.pos,
+            Err(Stop::End) => text.len(),
+            Ok(()) => {
+                parser.skip_whitespace();
+                parser.pos
+            }
+        };
+        valid as f32 / text.len() as f32
+    }
+}
+
+// Shannon entropy in bits per byte - 0 for a run of one byte, 8 for every
+// byte value equally often
+#[allow(dead_code)]
+pub fn entropy(data: &[u8]) -> f32 {
+    let mut counts = [0u32; 256];
+    for b in data {
+        counts[*b as usize] += 1;
+    }
+    let len = data.len() as f32;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+                 let p = *c as f32 / len;
+                 -p * p.log2()
+             })
+        .sum()
+}
+
+// Lower entropy scores higher, as structured data has less than random
+// bytes.  Xoring with a single byte only renames the byte values, so this
+// can't choose between single byte keys - it's for comparing whole
+// decryptions, say under different key sizes.
+#[allow(dead_code)]
+pub struct Entropy;
+
+impl Scorer for Entropy {
+    fn score(&self, text: &[u8]) -> f32 {
+        -entropy(text)
+    }
+}
+
+fn is_control(c: char) -> bool {
+    c.is_control() && !c.is_whitespace()
+}
+
+// The fraction of the bytes that are valid UTF-8 and not control characters.
+// A sequence cut off at the end counts as valid.
+pub struct Utf8;
+
+impl Scorer for Utf8 {
+    fn score(&self, text: &[u8]) -> f32 {
+        if text.is_empty() {
+            return 0.0;
+        }
+        let mut good = 0;
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (valid, skip) = match str::from_utf8(rest) {
+                Ok(s) => (s, rest.len()),
+                Err(e) => {
+                    let valid = str::from_utf8(&rest[..e.valid_up_to()]).unwrap();
+                    match e.error_len() {
+                        Some(len) => (valid, e.valid_up_to() + len),
+                        None => {
+                            good += rest.len() - e.valid_up_to();
+                            (valid, rest.len())
+                        }
+                    }
+                }
+            };
+            good += valid
+                .chars()
+                .filter(|c| !is_control(*c))
+                .map(|c| c.len_utf8())
+                .sum::<usize>();
+            rest = &rest[skip..];
+        }
+        good as f32 / text.len() as f32
+    }
+}
+
+// UTF-16 text, in whichever byte order scores better.  Scores the fraction of
+// the bytes that decode to printable Latin-1, with the rest of the Latin
+// blocks (up to U+024F) counting half.  Any single byte xor of ASCII in UTF-16
+// is just as valid, only in some other script, so without favouring the first
+// block every key would do.
+pub struct Utf16;
+
+impl Utf16 {
+    fn score_units<I: Iterator<Item = u16>>(units: I, len: usize) -> f32 {
+        let latin: f32 = decode_utf16(units)
+            .filter_map(|c| c.ok())
+            .filter(|c| !is_control(*c))
+            .map(|c| match c as u32 {
+                     0...0xff => 1.0,
+                     0x100...0x24f => 0.5,
+                     _ => 0.0,
+                 })
+            .sum();
+        2.0 * latin / len as f32
+    }
+}
+
+impl Scorer for Utf16 {
+    fn score(&self, text: &[u8]) -> f32 {
+        if text.len() < 2 {
+            return 0.0;
+        }
+        let little_endian = text.chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| pair[0] as u16 | (pair[1] as u16) << 8);
+        let big_endian = text.chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| (pair[0] as u16) << 8 | pair[1] as u16);
+        Utf16::score_units(little_endian, text.len())
+            .max(Utf16::score_units(big_endian, text.len()))
+    }
+}
+
+// the fraction of the bytes in the base64 alphabet (either flavour), padding
+// and line breaks included
+pub struct Base64;
+
+impl Scorer for Base64 {
+    fn score(&self, text: &[u8]) -> f32 {
+        if text.is_empty() {
+            return 0.0;
+        }
+        let in_alphabet = text.iter()
+            .filter(|b| match **b {
+                        b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' => true,
+                        b'+' | b'/' | b'-' | b'_' | b'=' | b'\r' | b'\n' => true,
+                        _ => false,
+                    })
+            .count();
+        in_alphabet as f32 / text.len() as f32
+    }
+}
+
+// A guess at what some bytes are: a known file type, JSON, base64, UTF-16 or
+// UTF-8 text, or None.
+pub fn identify(data: &[u8]) -> Option<&'static str> {
+    if let Some(name) = MagicBytes::detect(data) {
+        return Some(name);
+    }
+    if data.is_empty() {
+        None
+    } else if Json::is_valid(data) {
+        Some("JSON")
+    } else if Base64.score(data) == 1.0 {
+        Some("base64")
+    } else if data.contains(&0) && Utf16.score(data) > 0.95 {
+        Some("UTF-16")
+    } else if Utf8.score(data) > 0.95 {
+        Some("text")
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_magic_bytes() {
+    use bytes::{most_likely_xor, random_bytes, xor1};
+
+    let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+    png.extend(random_bytes(100));
+    assert_eq!(MagicBytes::detect(&png), Some("PNG"));
+    assert_eq!(MagicBytes::detect(b"%PDF-1.7"), Some("PDF"));
+    assert_eq!(MagicBytes::detect(b"hello"), None);
+    assert_eq!(MagicBytes.score(&png), 1.0);
+    assert_eq!(MagicBytes.score(b"PK"), 1.0);
+    assert_eq!(MagicBytes.score(b"P?"), 0.5);
+
+    let ciphertext = xor1(&png, 0x3c);
+    assert_eq!(most_likely_xor(&ciphertext, &MagicBytes).unwrap().0, 0x3c);
+}
+
+#[test]
+fn test_json() {
+    use bytes::{most_likely_xor, xor1};
+
+    let document = r#"{"name": "Ice", "tracks": [1, 2.5, -3e10, true, null], "x": {"é": ""}}"#
+        .as_bytes();
+    assert!(Json::is_valid(document));
+    assert!(Json::is_valid(b" [] "));
+    assert!(Json::is_valid(b"0"));
+    for invalid in [&b"{"[..], b"[1,]", b"{1: 2}", b"01", b"\"\x01\"", b"[] []", b"tru", b""]
+            .iter() {
+        assert!(!Json::is_valid(invalid), "{:?}", invalid);
+    }
+    assert!(!Json::is_valid(&[b'['; 1000]));
+
+    // cut off, it's still as good as it gets
+    assert_eq!(Json.score(&document[..30]), 1.0);
+    assert_eq!(Json.score(b"[1, 2, x]"), 7.0 / 9.0);
+
+    let ciphertext = xor1(document, 0x99);
+    assert_eq!(most_likely_xor(&ciphertext, &Json).unwrap().0, 0x99);
+}
+
+#[test]
+fn test_entropy() {
+    use bytes::random_bytes;
+
+    assert_eq!(entropy(&[7; 100]), 0.0);
+    assert_eq!(entropy(b"abababab"), 1.0);
+    let every_byte: Vec<u8> = (0..256).map(|b| b as u8).collect();
+    assert_eq!(entropy(&every_byte), 8.0);
+    assert!(Entropy.score(b"some text that repeats some text") >
+            Entropy.score(&random_bytes(32)));
+}
+
+#[test]
+fn test_text_encodings() {
+    use bytes::{most_likely_xor, xor1};
+
+    assert_eq!(Utf8.score("naïve café".as_bytes()), 1.0);
+    assert_eq!(Utf8.score(b"ab\xffcd"), 0.8);
+    assert_eq!(Utf8.score(b"ab\x01\x02"), 0.5);
+    // the start of a two byte character, cut off
+    assert_eq!(Utf8.score(b"abc\xc3"), 1.0);
+
+    let text: Vec<u8> = "Grüße aus Köln, and hello from UTF-16"
+        .encode_utf16()
+        .flat_map(|u| vec![u as u8, (u >> 8) as u8])
+        .collect();
+    assert_eq!(Utf16.score(&text), 1.0);
+    assert_eq!(most_likely_xor(&xor1(&text, 0x41), &Utf16).unwrap().0, 0x41);
+
+    assert_eq!(Base64.score(b"aGVsbG8gd29ybGQ="), 1.0);
+    assert_eq!(Base64.score(b"hello world!"), 10.0 / 12.0);
+}
+
+#[test]
+fn test_identify() {
+    let utf16: Vec<u8> = "hello".encode_utf16().flat_map(|u| vec![u as u8, 0]).collect();
+    assert_eq!(identify(b"<?xml version=\"1.0\"?><a/>"), Some("XML"));
+    assert_eq!(identify(b"{\"a\": [1, 2]}"), Some("JSON"));
+    assert_eq!(identify(b"aGVsbG8gd29ybGQ="), Some("base64"));
+    assert_eq!(identify(&utf16), Some("UTF-16"));
+    assert_eq!(identify("just some text".as_bytes()), Some("text"));
+    assert_eq!(identify(b"\x00\x01\x02\x03\xff\xfe\xfd"), None);
+}
@@ -0,0 +1,328 @@
+use aes::{apply_ctr_layout_at, CtrLayout};
+use aescore::{Aes, BLOCK_SIZE};
+use blockcipher::CipherError;
+
+// AES-GCM (NIST SP 800-38D).  The encryption is plain CTR mode with a 32 bit
+// big endian counter; the authentication is GHASH, a polynomial evaluated at
+// the hash key H in GF(2^128).  GHASH and the field multiplication are public
+// so that nonce-reuse attacks can be built on them.
+
+#[allow(dead_code)]
+pub const TAG_SIZE: usize = 16;
+
+#[allow(dead_code)]
+fn to_u128(block: &[u8]) -> u128 {
+    block.iter().fold(0u128, |acc, b| (acc << 8) | *b as u128)
+}
+
+#[allow(dead_code)]
+fn from_u128(val: u128) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        block[i] = (val >> (8 * (BLOCK_SIZE - 1 - i))) as u8;
+    }
+    block
+}
+
+// Multiplication in GF(2^128) modulo x^128 + x^7 + x^2 + x + 1.  GCM
+// numbers its bits backwards - the most significant bit of the first byte is
+// the coefficient of x^0 - which is why the reduction constant is at the top.
+#[allow(dead_code)]
+pub fn gf128_mul(x: u128, y: u128) -> u128 {
+    let r = 0xe1u128 << 120;
+    let mut z = 0u128;
+    let mut v = y;
+    for i in 0..128 {
+        if (x >> (127 - i)) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ r } else { v >> 1 };
+    }
+    z
+}
+
+// GHASH of the associated data and ciphertext (each zero padded to a whole
+// block), followed by a block holding both of their lengths in bits
+#[allow(dead_code)]
+pub fn ghash(h: &[u8], aad: &[u8], ciphertext: &[u8]) -> [u8; BLOCK_SIZE] {
+    let h = to_u128(h);
+    let mut y = 0u128;
+    for data in [aad, ciphertext].iter() {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let mut block = [0u8; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            y = gf128_mul(y ^ to_u128(&block), h);
+        }
+    }
+    let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    from_u128(gf128_mul(y ^ lengths, h))
+}
+
+// the hash key, H = E(K, 0)
+#[allow(dead_code)]
+pub fn hash_key(aes: &Aes) -> [u8; BLOCK_SIZE] {
+    let mut h = [0u8; BLOCK_SIZE];
+    aes.encrypt_block(&mut h);
+    h
+}
+
+// The first counter block.  A 96 bit IV just gets a counter of 1 appended;
+// anything else is GHASHed.
+#[allow(dead_code)]
+fn initial_counter(h: &[u8], iv: &[u8]) -> Result<[u8; BLOCK_SIZE], CipherError> {
+    if iv.is_empty() {
+        return Err(CipherError::InvalidLength);
+    }
+    if iv.len() == 12 {
+        let mut j0 = [0u8; BLOCK_SIZE];
+        j0[..12].copy_from_slice(iv);
+        j0[15] = 1;
+        Ok(j0)
+    } else {
+        Ok(ghash(h, &[], iv))
+    }
+}
+
+#[allow(dead_code)]
+fn compute_tag(aes: &Aes,
+               h: &[u8],
+               j0: &[u8],
+               aad: &[u8],
+               ciphertext: &[u8])
+               -> [u8; TAG_SIZE] {
+    let mut tag = ghash(h, aad, ciphertext);
+    let mut encrypted_j0 = [0u8; BLOCK_SIZE];
+    encrypted_j0.copy_from_slice(j0);
+    aes.encrypt_block(&mut encrypted_j0);
+    for (t, e) in tag.iter_mut().zip(encrypted_j0.iter()) {
+        *t ^= *e;
+    }
+    tag
+}
+
+// CTR from the counter block after J0.  With a hashed IV the counter can
+// start anywhere, so it may well wrap partway through.
+#[allow(dead_code)]
+fn gctr(aes: &Aes, j0: &[u8], data: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let mut output = data.to_vec();
+    try!(apply_ctr_layout_at(aes, &CtrLayout::inc32(), j0, BLOCK_SIZE as u64, &mut output));
+    Ok(output)
+}
+
+// returns (ciphertext, tag)
+#[allow(dead_code)]
+pub fn gcm_encrypt(key: &[u8],
+                   iv: &[u8],
+                   aad: &[u8],
+                   cleartext: &[u8])
+                   -> Result<(Vec<u8>, [u8; TAG_SIZE]), CipherError> {
+    let aes = try!(Aes::new(key));
+    let h = hash_key(&aes);
+    let j0 = try!(initial_counter(&h, iv));
+    let ciphertext = try!(gctr(&aes, &j0, cleartext));
+    let tag = compute_tag(&aes, &h, &j0, aad, &ciphertext);
+    Ok((ciphertext, tag))
+}
+
+// Checks the tag before decrypting anything.  The tag can be cut down to its
+// first 12 to 16 bytes, or 8 or 4 for the applications SP 800-38D appendix C
+// allows them in.
+#[allow(dead_code)]
+pub fn gcm_decrypt(key: &[u8],
+                   iv: &[u8],
+                   aad: &[u8],
+                   ciphertext: &[u8],
+                   tag: &[u8])
+                   -> Result<Vec<u8>, CipherError> {
+    match tag.len() {
+        4 | 8 | 12...TAG_SIZE => {}
+        _ => return Err(CipherError::InvalidLength),
+    }
+    let aes = try!(Aes::new(key));
+    let h = hash_key(&aes);
+    let j0 = try!(initial_counter(&h, iv));
+    let expected_tag = compute_tag(&aes, &h, &j0, aad, ciphertext);
+
+    // compare every byte of the tag there is, rather than stopping at the first
+    // difference
+    let difference = expected_tag
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return Err(CipherError::AuthenticationFailed);
+    }
+
+    gctr(&aes, &j0, ciphertext)
+}
+
+#[cfg(test)]
+fn gcm_vector(key: &str, iv: &str, aad: &str, cleartext: &str, ciphertext: &str, tag: &str) {
+    use hexstring::{fromhex, tohex};
+
+    let key = fromhex(key).unwrap();
+    let iv = fromhex(iv).unwrap();
+    let aad = fromhex(aad).unwrap();
+    let (encrypted, computed_tag) = gcm_encrypt(&key, &iv, &aad, &fromhex(cleartext).unwrap())
+        .unwrap();
+    assert_eq!(tohex(&encrypted), ciphertext);
+    assert_eq!(tohex(&computed_tag), tag);
+
+    let decrypted = gcm_decrypt(&key, &iv, &aad, &encrypted, &computed_tag).unwrap();
+    assert_eq!(tohex(&decrypted), cleartext);
+}
+
+#[cfg(test)]
+static TEST_KEY: &'static str = "feffe9928665731c6d6a8f9467308308";
+
+#[cfg(test)]
+static TEST_CLEARTEXT: &'static str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d\
+                                       8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657\
+                                       ba637b39";
+
+#[cfg(test)]
+static TEST_CIPHERTEXT: &'static str = "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e23\
+                                        29aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac97\
+                                        3d58e091";
+
+// test cases 1-4 from the GCM spec, all 96 bit IVs
+#[test]
+fn test_gcm_vectors() {
+    gcm_vector("00000000000000000000000000000000",
+               "000000000000000000000000",
+               "",
+               "",
+               "",
+               "58e2fccefa7e3061367f1d57a4e7455a");
+    gcm_vector("00000000000000000000000000000000",
+               "000000000000000000000000",
+               "",
+               "00000000000000000000000000000000",
+               "0388dace60b6a392f328c2b971b2fe78",
+               "ab6e47d42cec13bdf53a67b21257bddf");
+    gcm_vector(TEST_KEY,
+               "cafebabefacedbaddecaf888",
+               "",
+               &format!("{}{}", TEST_CLEARTEXT, "1aafd255"),
+               &format!("{}{}", TEST_CIPHERTEXT, "473f5985"),
+               "4d5c2af327cd64a62cf35abd2ba6fab4");
+    gcm_vector(TEST_KEY,
+               "cafebabefacedbaddecaf888",
+               "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+               TEST_CLEARTEXT,
+               TEST_CIPHERTEXT,
+               "5bc94fbc3221a5db94fae95ae7121a47");
+}
+
+// test case 4 with the tag truncated to 96 and 32 bits - the first bytes of
+// the full tag
+#[test]
+fn test_gcm_truncated_tags() {
+    use hexstring::fromhex;
+
+    let key = fromhex(TEST_KEY).unwrap();
+    let iv = fromhex("cafebabefacedbaddecaf888").unwrap();
+    let aad = fromhex("feedfacedeadbeeffeedfacedeadbeefabaddad2").unwrap();
+    let ciphertext = fromhex(TEST_CIPHERTEXT).unwrap();
+    for tag in ["5bc94fbc3221a5db94fae95a", "5bc94fbc"].iter() {
+        let tag = fromhex(tag).unwrap();
+        assert_eq!(gcm_decrypt(&key, &iv, &aad, &ciphertext, &tag).unwrap(),
+                   fromhex(TEST_CLEARTEXT).unwrap());
+    }
+    assert_eq!(gcm_decrypt(&key, &iv, &aad, &ciphertext, &fromhex("5bc94fbc3221a5db94fae95b")
+                                                               .unwrap()),
+               Err(CipherError::AuthenticationFailed));
+}
+
+// test cases 5 and 6 - IVs that aren't 96 bits get hashed
+#[test]
+fn test_gcm_long_and_short_ivs() {
+    gcm_vector(TEST_KEY,
+               "cafebabefacedbad",
+               "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+               TEST_CLEARTEXT,
+               "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+                73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+               "3612d2e79e3b0785561be14aaca2fccb");
+    gcm_vector(TEST_KEY,
+               "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+                c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+               "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+               TEST_CLEARTEXT,
+               "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+                01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+               "619cc5aefffe0bfa462af43c1699d050");
+}
+
+#[test]
+fn test_gcm_tampering() {
+    use bytes::random_bytes;
+
+    let key = random_bytes(16);
+    let iv = random_bytes(12);
+    let (mut ciphertext, tag) = gcm_encrypt(&key, &iv, b"header", b"attack at dawn").unwrap();
+    assert!(gcm_decrypt(&key, &iv, b"header", &ciphertext, &tag).is_ok());
+    assert_eq!(gcm_decrypt(&key, &iv, b"footer", &ciphertext, &tag),
+               Err(CipherError::AuthenticationFailed));
+    assert_eq!(gcm_decrypt(&key, &iv, b"header", &ciphertext, &tag[..11]),
+               Err(CipherError::InvalidLength));
+    assert_eq!(gcm_decrypt(&key, &iv, b"header", &ciphertext, &tag[..5]),
+               Err(CipherError::InvalidLength));
+
+    ciphertext[0] ^= 1;
+    assert_eq!(gcm_decrypt(&key, &iv, b"header", &ciphertext, &tag),
+               Err(CipherError::AuthenticationFailed));
+}
+
+#[test]
+fn test_gf128_mul() {
+    // multiplying by 1 (the top bit, in GCM's order) changes nothing
+    let one = 1u128 << 127;
+    let x = 0x66e94bd4ef8a2c3b884cfa59ca342b2eu128;
+    assert_eq!(gf128_mul(x, one), x);
+    assert_eq!(gf128_mul(one, x), x);
+    assert_eq!(gf128_mul(x, 0), 0);
+}
+
+// a 64 byte IV whose J0 has a counter of 0xfffffffe, so the counter wraps
+// after the first block.  GHASH is linear in each IV block, so the last one
+// can be solved for whatever J0 is wanted.
+#[test]
+fn test_gcm_counter_wraps() {
+    use hexstring::fromhex;
+
+    let key = fromhex(TEST_KEY).unwrap();
+    let aes = Aes::new(&key).unwrap();
+    let h = to_u128(&hash_key(&aes));
+
+    // H^-1 = H^(2^128 - 2): square H 127 times, multiplying in all but the last
+    let one = 1u128 << 127;
+    let mut h_inverse = one;
+    let mut power = h;
+    for i in 0..128 {
+        if i > 0 {
+            h_inverse = gf128_mul(h_inverse, power);
+        }
+        power = gf128_mul(power, power);
+    }
+    assert_eq!(gf128_mul(h, h_inverse), one);
+
+    let j0 = from_u128(0x0123456789abcdef01234567fffffffe);
+    let mut iv = vec![0x5a; 64];
+    let prefix = iv[..48]
+        .chunks(BLOCK_SIZE)
+        .fold(0u128, |y, block| gf128_mul(y ^ to_u128(block), h));
+    let last = gf128_mul(gf128_mul(to_u128(&j0), h_inverse) ^ (64 * 8), h_inverse) ^ prefix;
+    iv[48..].copy_from_slice(&from_u128(last));
+    assert_eq!(initial_counter(&from_u128(h), &iv).unwrap(), j0);
+
+    let cleartext = [0u8; 3 * BLOCK_SIZE];
+    let (ciphertext, tag) = gcm_encrypt(&key, &iv, b"", &cleartext).unwrap();
+    for (i, counter) in [[0xff; 4], [0, 0, 0, 0], [0, 0, 0, 1]].iter().enumerate() {
+        let mut keystream = j0;
+        keystream[12..].copy_from_slice(counter);
+        aes.encrypt_block(&mut keystream);
+        assert_eq!(&ciphertext[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE], &keystream[..]);
+    }
+    assert_eq!(gcm_decrypt(&key, &iv, b"", &ciphertext, &tag).unwrap(), &cleartext[..]);
+}
@@ -0,0 +1,209 @@
+use bytes::{hamming_distance, transpose};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Ways of guessing the length of a repeating xor key from the ciphertext
+// alone.  Each estimator rates every length in `lengths` that leaves at least
+// two whole blocks of ciphertext, and returns them best first with a
+// confidence: the length's share of how much better than the worst length
+// each of them rated, so the confidences add up to 1.
+//
+// Every multiple of the key length rates about as well as the key length
+// itself, so a length is only ranked above its divisors if it rates clearly
+// better than them - by more than TOLERANCE of the gap between the best and
+// the median rating.
+const TOLERANCE: f32 = 0.3;
+
+// Turns ratings (higher is better), shortest length first, into ranked
+// confidences.  A length rated about as well as one of its divisors is put
+// just below the divisor.
+fn rank(ratings: Vec<(usize, f32)>) -> Vec<(usize, f32)> {
+    let mut sorted = ratings.iter().map(|r| r.1).collect::<Vec<_>>();
+    sorted.sort_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Equal));
+    if sorted.is_empty() {
+        return ratings;
+    }
+    let worst = sorted[0];
+    let tolerance = TOLERANCE * (sorted[sorted.len() - 1] - sorted[sorted.len() / 2]);
+
+    let mut adjusted = ratings;
+    for i in 0..adjusted.len() {
+        let (len, rating) = adjusted[i];
+        let divisor_rating = adjusted[..i]
+            .iter()
+            .filter(|&&(d, r)| len % d == 0 && r >= rating - tolerance)
+            .map(|r| r.1)
+            .fold(f32::INFINITY, f32::min);
+        if divisor_rating.is_finite() {
+            adjusted[i].1 = (divisor_rating - tolerance).max(worst).min(rating);
+        }
+    }
+
+    let total: f32 = adjusted.iter().map(|r| r.1 - worst).sum();
+    let mut ranked = adjusted
+        .into_iter()
+        .map(|(len, rating)| {
+                 let confidence = if total > 0.0 { (rating - worst) / total } else { 0.0 };
+                 (len, confidence)
+             })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap_or(Ordering::Equal));
+    ranked
+}
+
+// Rates each length that leaves two whole blocks of ciphertext.
+fn rate<F: Fn(usize) -> f32>(ciphertext: &[u8], lengths: Range<usize>, rating: F)
+                             -> Vec<(usize, f32)> {
+    rank(lengths
+             .filter(|&len| len > 0 && 2 * len <= ciphertext.len())
+             .map(|len| (len, rating(len)))
+             .collect())
+}
+
+// how many blocks hamming compares - every pair of them is, so all of a long
+// ciphertext would take far too long
+const HAMMING_BLOCKS: usize = 64;
+
+// The Hamming distance between every pair of the first blocks, in bits per
+// byte and averaged.  Bytes xored with the same key byte differ only as much
+// as the cleartext bytes do, which for text is less than random bytes do.
+pub fn hamming(ciphertext: &[u8], lengths: Range<usize>) -> Vec<(usize, f32)> {
+    rate(ciphertext, lengths, |len| {
+        let blocks = ciphertext
+            .chunks(len)
+            .filter(|b| b.len() == len)
+            .take(HAMMING_BLOCKS)
+            .collect::<Vec<_>>();
+        let mut total = 0u64;
+        let mut pairs = 0u64;
+        for (i, a) in blocks.iter().enumerate() {
+            for b in &blocks[i + 1..] {
+                total += hamming_distance(a, b) as u64;
+                pairs += 1;
+            }
+        }
+        -(total as f32 / (pairs * len as u64) as f32)
+    })
+}
+
+// The chance that two bytes picked from one column are the same, averaged
+// over the columns.  Each column of the right length is a single byte xor of
+// text, as uneven as text is, and xor doesn't change that; the wrong length
+// mixes key bytes and flattens it.
+pub fn index_of_coincidence(ciphertext: &[u8], lengths: Range<usize>) -> Vec<(usize, f32)> {
+    rate(ciphertext, lengths, |len| {
+        let total: f32 = transpose(ciphertext, len).iter().map(|c| coincidence(c)).sum();
+        total / len as f32
+    })
+}
+
+fn coincidence(bytes: &[u8]) -> f32 {
+    if bytes.len() < 2 {
+        return 0.0;
+    }
+    let mut counts = [0u32; 256];
+    for b in bytes {
+        counts[*b as usize] += 1;
+    }
+    let same: u32 = counts.iter().map(|c| c * c.saturating_sub(1)).sum();
+    same as f32 / (bytes.len() * (bytes.len() - 1)) as f32
+}
+
+// Kasiski examination: a repeated trigram is usually the same cleartext
+// under the same part of the key, so the distance between the repeats is a
+// multiple of the key length.  Rates each length by the fraction of those
+// distances it divides, times the length - 1 for a length that only divides
+// them as often as chance would.  Short ciphertexts don't repeat enough to
+// go on.
+pub fn kasiski(ciphertext: &[u8], lengths: Range<usize>) -> Vec<(usize, f32)> {
+    let mut last_seen = HashMap::new();
+    let mut distances = Vec::new();
+    for (i, trigram) in ciphertext.windows(3).enumerate() {
+        if let Some(previous) = last_seen.insert(trigram, i) {
+            distances.push(i - previous);
+        }
+    }
+
+    rate(ciphertext, lengths, |len| {
+        let divided = distances.iter().filter(|&d| d % len == 0).count();
+        (divided * len) as f32 / distances.len().max(1) as f32
+    })
+}
+
+// The fraction of bytes equal to the byte `len` further on.  Shifting by the
+// key length lines each byte up with one under the same key byte, where they
+// match as often as the cleartext's do.
+pub fn autocorrelation(ciphertext: &[u8], lengths: Range<usize>) -> Vec<(usize, f32)> {
+    rate(ciphertext, lengths, |len| {
+        let matches = ciphertext
+            .iter()
+            .zip(&ciphertext[len..])
+            .filter(|&(a, b)| a == b)
+            .count();
+        matches as f32 / (ciphertext.len() - len) as f32
+    })
+}
+
+// All four estimators, their confidences averaged and ranked again.
+pub fn key_lengths(ciphertext: &[u8], lengths: Range<usize>) -> Vec<(usize, f32)> {
+    let estimates = [hamming(ciphertext, lengths.clone()),
+                     index_of_coincidence(ciphertext, lengths.clone()),
+                     kasiski(ciphertext, lengths.clone()),
+                     autocorrelation(ciphertext, lengths)];
+    let mut combined = HashMap::new();
+    for estimate in estimates.iter() {
+        for &(len, confidence) in estimate {
+            *combined.entry(len).or_insert(0.0) += confidence / estimates.len() as f32;
+        }
+    }
+    let mut combined = combined.into_iter().collect::<Vec<_>>();
+    combined.sort_by_key(|c| c.0);
+    rank(combined)
+}
+
+
+#[cfg(test)]
+fn challenge6_ciphertext() -> Vec<u8> {
+    use base64::{decode_config, MIME};
+    decode_config(include_str!("challenge-6.dat"), MIME).unwrap()
+}
+
+#[test]
+fn test_estimators() {
+    let ciphertext = challenge6_ciphertext();
+    let estimates = [hamming(&ciphertext, 2..41),
+                     index_of_coincidence(&ciphertext, 2..41),
+                     kasiski(&ciphertext, 2..41),
+                     autocorrelation(&ciphertext, 2..41),
+                     key_lengths(&ciphertext, 2..41)];
+    for estimate in estimates.iter() {
+        assert_eq!(estimate.len(), 39);
+        assert_eq!(estimate[0].0, 29);
+        assert!(estimate.windows(2).all(|w| w[0].1 >= w[1].1));
+        let total: f32 = estimate.iter().map(|e| e.1).sum();
+        assert!((total - 1.0).abs() < 0.001);
+    }
+
+    // lengths without two blocks of ciphertext aren't rated
+    assert_eq!(hamming(&ciphertext[..20], 1..41).len(), 10);
+    assert!(kasiski(b"", 1..41).is_empty());
+}
+
+#[test]
+fn test_multiples() {
+    use bytes::repeat_xor;
+
+    // 6, 12, 18... all fit this key; 6 should come first
+    let cleartext = repeat_xor(&challenge6_ciphertext(), b"Terminator X: Bring the noise");
+    let ciphertext = repeat_xor(&cleartext[..600], b"\x13\x37\xde\xad\xbe\xef");
+    for estimate in [hamming(&ciphertext, 1..41),
+                     index_of_coincidence(&ciphertext, 1..41),
+                     key_lengths(&ciphertext, 1..41)]
+                .iter() {
+        assert_eq!(estimate[0].0, 6);
+        let position = |len| estimate.iter().position(|e| e.0 == len).unwrap();
+        assert!(position(6) < position(12));
+        assert!(position(12) < position(24));
+    }
+}
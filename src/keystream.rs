@@ -0,0 +1,76 @@
+use blockcipher::{BlockCipher, CipherError};
+use chacha20::ChaCha20;
+use rc4::Rc4;
+use salsa20::Salsa20;
+use stream::CtrStream;
+
+// Anything that turns a key (and nonce) into a stream of bytes to xor with the
+// data.  Each call carries on where the last one stopped.
+pub trait Keystream {
+    // xor the next data.len() bytes of keystream into data
+    fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), CipherError>;
+}
+
+// the next len bytes of keystream on their own
+pub fn keystream<K: Keystream>(generator: &mut K, len: usize) -> Result<Vec<u8>, CipherError> {
+    let mut bytes = vec![0u8; len];
+    try!(generator.apply_keystream(&mut bytes));
+    Ok(bytes)
+}
+
+impl Keystream for Rc4 {
+    fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), CipherError> {
+        Rc4::apply_keystream(self, data);
+        Ok(())
+    }
+}
+
+impl Keystream for Salsa20 {
+    fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), CipherError> {
+        Salsa20::apply_keystream(self, data);
+        Ok(())
+    }
+}
+
+impl Keystream for ChaCha20 {
+    fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), CipherError> {
+        ChaCha20::apply_keystream(self, data)
+    }
+}
+
+// CTR mode with any block cipher.  The inner stream doesn't matter here - use
+// `CtrStream::new(cipher, nonce, ())` for a bare keystream.
+impl<C: BlockCipher, S> Keystream for CtrStream<C, S> {
+    fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), CipherError> {
+        CtrStream::apply_keystream(self, data);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_keystreams() {
+    use aes::encrypt_ctr;
+    use aescore::Aes;
+    use bytes::random_bytes;
+    use chacha20::chacha20;
+    use rc4::rc4;
+    use salsa20::salsa20;
+
+    let key = random_bytes(32);
+    let zeros = vec![0u8; 100];
+    let mut ctr = CtrStream::new(Aes::new(&key[..16]).unwrap(), 5, ()).unwrap();
+    assert_eq!(keystream(&mut ctr, 100).unwrap(),
+               encrypt_ctr(&zeros, &key[..16], &5).unwrap());
+    let mut salsa = Salsa20::new(&key, &[1; 8]).unwrap();
+    assert_eq!(keystream(&mut salsa, 100).unwrap(),
+               salsa20(&zeros, &key, &[1; 8]).unwrap());
+    let mut chacha = ChaCha20::new(&key, &[2; 12], 0).unwrap();
+    assert_eq!(keystream(&mut chacha, 100).unwrap(),
+               chacha20(&zeros, &key, &[2; 12], 0).unwrap());
+
+    // and it carries on from where it was
+    let mut arc4 = Rc4::new(&key).unwrap();
+    let mut both = keystream(&mut arc4, 40).unwrap();
+    both.extend(keystream(&mut arc4, 60).unwrap());
+    assert_eq!(both, rc4(&zeros, &key).unwrap());
+}
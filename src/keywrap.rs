@@ -0,0 +1,244 @@
+use aescore::Aes;
+use blockcipher::{BlockCipher, CipherError};
+
+// AES key wrap (RFC 3394) and key wrap with padding (RFC 5649).  The key being
+// wrapped is split into 64 bit halves of a block, which are run through the
+// cipher six times each along with a running integrity check value.  Unwrapping
+// fails with AuthenticationFailed if the check value doesn't come out right -
+// the wrong KEK or a corrupted blob.
+
+#[allow(dead_code)]
+static DEFAULT_IV: [u8; 8] = [0xa6; 8];
+#[allow(dead_code)]
+static PADDED_IV_PREFIX: [u8; 4] = [0xa6, 0x59, 0x59, 0xa6];
+
+// The key must be at least two 64 bit blocks.
+#[allow(dead_code)]
+pub fn wrap_key(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    wrap_key_with(&try!(Aes::new(kek)), key)
+}
+
+#[allow(dead_code)]
+pub fn unwrap_key(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, CipherError> {
+    unwrap_key_with(&try!(Aes::new(kek)), wrapped)
+}
+
+// RFC 5649 - any key length from one byte up
+#[allow(dead_code)]
+pub fn wrap_key_with_padding(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    wrap_key_with_padding_with(&try!(Aes::new(kek)), key)
+}
+
+#[allow(dead_code)]
+pub fn unwrap_key_with_padding(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, CipherError> {
+    unwrap_key_with_padding_with(&try!(Aes::new(kek)), wrapped)
+}
+
+#[allow(dead_code)]
+fn check_cipher<C: BlockCipher>(cipher: &C) -> Result<(), CipherError> {
+    if cipher.block_size() != 16 {
+        Err(CipherError::InvalidLength)
+    } else {
+        Ok(())
+    }
+}
+
+// xor the step number t into the check value
+#[allow(dead_code)]
+fn xor_step(a: &mut [u8], t: u64) {
+    for (i, b) in a.iter_mut().enumerate() {
+        *b ^= (t >> (8 * (7 - i))) as u8;
+    }
+}
+
+// the wrapping function W from RFC 3394 section 2.2.1, with iv as the initial
+// check value
+#[allow(dead_code)]
+fn wrap<C: BlockCipher>(cipher: &C, iv: &[u8], key: &[u8]) -> Vec<u8> {
+    let n = key.len() / 8;
+    let mut a = iv.to_vec();
+    let mut r = key.to_vec();
+    let mut block = [0u8; 16];
+    for j in 0..6 {
+        for i in 0..n {
+            block[..8].copy_from_slice(&a);
+            block[8..].copy_from_slice(&r[i * 8..i * 8 + 8]);
+            cipher.encrypt_block(&mut block);
+            a.copy_from_slice(&block[..8]);
+            xor_step(&mut a, (n * j + i + 1) as u64);
+            r[i * 8..i * 8 + 8].copy_from_slice(&block[8..]);
+        }
+    }
+    a.extend(r);
+    a
+}
+
+// W^-1, returning the check value and the key
+#[allow(dead_code)]
+fn unwrap<C: BlockCipher>(cipher: &C, wrapped: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let n = wrapped.len() / 8 - 1;
+    let mut a = wrapped[..8].to_vec();
+    let mut r = wrapped[8..].to_vec();
+    let mut block = [0u8; 16];
+    for j in (0..6).rev() {
+        for i in (0..n).rev() {
+            xor_step(&mut a, (n * j + i + 1) as u64);
+            block[..8].copy_from_slice(&a);
+            block[8..].copy_from_slice(&r[i * 8..i * 8 + 8]);
+            cipher.decrypt_block(&mut block);
+            a.copy_from_slice(&block[..8]);
+            r[i * 8..i * 8 + 8].copy_from_slice(&block[8..]);
+        }
+    }
+    (a, r)
+}
+
+// compare every byte, rather than stopping at the first difference
+#[allow(dead_code)]
+fn check_value_matches(a: &[u8], expected: &[u8]) -> bool {
+    a.iter().zip(expected).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[allow(dead_code)]
+pub fn wrap_key_with<C: BlockCipher>(cipher: &C, key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    try!(check_cipher(cipher));
+    if key.len() < 16 || key.len() % 8 != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+    Ok(wrap(cipher, &DEFAULT_IV, key))
+}
+
+#[allow(dead_code)]
+pub fn unwrap_key_with<C: BlockCipher>(cipher: &C,
+                                       wrapped: &[u8])
+                                       -> Result<Vec<u8>, CipherError> {
+    try!(check_cipher(cipher));
+    if wrapped.len() < 24 || wrapped.len() % 8 != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+    let (a, key) = unwrap(cipher, wrapped);
+    if !check_value_matches(&a, &DEFAULT_IV) {
+        return Err(CipherError::AuthenticationFailed);
+    }
+    Ok(key)
+}
+
+// The check value holds the key's real length, and the key is zero padded to
+// a multiple of 64 bits.  A key that pads to a single 64 bit block is just
+// encrypted along with the check value as one block.
+#[allow(dead_code)]
+pub fn wrap_key_with_padding_with<C: BlockCipher>(cipher: &C,
+                                                  key: &[u8])
+                                                  -> Result<Vec<u8>, CipherError> {
+    try!(check_cipher(cipher));
+    if key.is_empty() || key.len() > u32::max_value() as usize {
+        return Err(CipherError::InvalidLength);
+    }
+    let mut iv = PADDED_IV_PREFIX.to_vec();
+    for i in (0..4).rev() {
+        iv.push((key.len() >> (8 * i)) as u8);
+    }
+    let mut padded = key.to_vec();
+    let padded_len = (key.len() + 7) / 8 * 8;
+    padded.resize(padded_len, 0);
+
+    if padded.len() == 8 {
+        let mut block = iv;
+        block.extend(padded);
+        cipher.encrypt_block(&mut block);
+        Ok(block)
+    } else {
+        Ok(wrap(cipher, &iv, &padded))
+    }
+}
+
+#[allow(dead_code)]
+pub fn unwrap_key_with_padding_with<C: BlockCipher>(cipher: &C,
+                                                    wrapped: &[u8])
+                                                    -> Result<Vec<u8>, CipherError> {
+    try!(check_cipher(cipher));
+    if wrapped.len() < 16 || wrapped.len() % 8 != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+    let (a, mut key) = if wrapped.len() == 16 {
+        let mut block = wrapped.to_vec();
+        cipher.decrypt_block(&mut block);
+        let key = block.split_off(8);
+        (block, key)
+    } else {
+        unwrap(cipher, wrapped)
+    };
+
+    // the length has to account for all but the last 0-7 bytes, which have to
+    // be zeros
+    let len = a[4..].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+    let valid = check_value_matches(&a[..4], &PADDED_IV_PREFIX) && len <= key.len() &&
+                len + 8 > key.len() && key[len..].iter().all(|b| *b == 0);
+    if !valid {
+        return Err(CipherError::AuthenticationFailed);
+    }
+    key.truncate(len);
+    Ok(key)
+}
+
+#[test]
+fn test_rfc3394() {
+    use hexstring::{fromhex, tohex};
+
+    let kek = fromhex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
+        .unwrap();
+    let key = fromhex("00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f")
+        .unwrap();
+    // (KEK length, key length, wrapped) from section 4
+    let vectors = [(16, 16, "1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5"),
+                   (24, 16, "96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d"),
+                   (32, 16, "64e8c3f9ce0f5ba263e9777905818a2a93c8191e7d6e8ae7"),
+                   (24, 24, "031d33264e15d33268f24ec260743edce1c6c7ddee725a936ba814915c6762d2"),
+                   (32, 24, "a8f9bc1612c68b3ff6e6f4fbe30e71e4769c8b80a32cb8958cd5d17d6b254da1"),
+                   (32,
+                    32,
+                    "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43b\
+                     fb988b9b7a02dd21")];
+    for &(kek_len, key_len, expected) in vectors.iter() {
+        let wrapped = wrap_key(&kek[..kek_len], &key[..key_len]).unwrap();
+        assert_eq!(tohex(&wrapped), expected);
+        assert_eq!(unwrap_key(&kek[..kek_len], &wrapped).unwrap(), &key[..key_len]);
+    }
+
+    let mut wrapped = wrap_key(&kek[..16], &key[..16]).unwrap();
+    assert_eq!(unwrap_key(&kek[..24], &wrapped), Err(CipherError::AuthenticationFailed));
+    wrapped[20] ^= 1;
+    assert_eq!(unwrap_key(&kek[..16], &wrapped), Err(CipherError::AuthenticationFailed));
+    assert_eq!(wrap_key(&kek[..16], &key[..12]), Err(CipherError::InvalidLength));
+}
+
+#[test]
+fn test_rfc5649() {
+    use hexstring::{fromhex, tohex};
+    use bytes::random_bytes;
+
+    let kek = fromhex("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8").unwrap();
+    let vectors = [("c37b7e6492584340bed12207808941155068f738",
+                    "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a"),
+                   ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f")];
+    for &(key, expected) in vectors.iter() {
+        let key = fromhex(key).unwrap();
+        let wrapped = wrap_key_with_padding(&kek, &key).unwrap();
+        assert_eq!(tohex(&wrapped), expected);
+        assert_eq!(unwrap_key_with_padding(&kek, &wrapped).unwrap(), key);
+    }
+
+    for len in 1..40 {
+        let key = random_bytes(len);
+        let mut wrapped = wrap_key_with_padding(&kek, &key).unwrap();
+        assert_eq!(unwrap_key_with_padding(&kek, &wrapped).unwrap(), key);
+        wrapped[3] ^= 0x80;
+        assert_eq!(unwrap_key_with_padding(&kek, &wrapped),
+                   Err(CipherError::AuthenticationFailed));
+    }
+
+    // a plain RFC 3394 blob has the wrong check value
+    let wrapped = wrap_key(&kek, &random_bytes(16)).unwrap();
+    assert_eq!(unwrap_key_with_padding(&kek, &wrapped),
+               Err(CipherError::AuthenticationFailed));
+}
@@ -0,0 +1,190 @@
+use scoring::{ChiSquared, NgramModel, Scorer};
+use std::io;
+
+// Models of languages other than English, and telling them apart.  The
+// built-in models are letter frequencies (from Wikipedia's tables, as a
+// percentage of letters); better ones can be trained from a corpus of each
+// language with NgramModel.
+
+static GERMAN: &'static [(char, f32)] =
+    &[('e', 16.396), ('n', 9.776), ('s', 7.270), ('r', 7.003), ('i', 6.550), ('a', 6.516),
+      ('t', 6.154), ('d', 5.076), ('h', 4.577), ('u', 4.166), ('l', 3.437), ('g', 3.009),
+      ('c', 2.732), ('o', 2.594), ('m', 2.534), ('w', 1.921), ('b', 1.886), ('f', 1.656),
+      ('k', 1.417), ('z', 1.134), ('ü', 0.995), ('v', 0.846), ('p', 0.670), ('ä', 0.578),
+      ('ö', 0.443), ('ß', 0.307), ('j', 0.268), ('y', 0.039), ('x', 0.034), ('q', 0.018)];
+
+static FRENCH: &'static [(char, f32)] =
+    &[('e', 14.715), ('s', 7.948), ('a', 7.636), ('i', 7.529), ('t', 7.244), ('n', 7.095),
+      ('r', 6.693), ('u', 6.311), ('o', 5.796), ('l', 5.456), ('d', 3.669), ('c', 3.260),
+      ('m', 2.968), ('p', 2.521), ('v', 1.838), ('é', 1.504), ('q', 1.362), ('f', 1.066),
+      ('b', 0.901), ('g', 0.866), ('h', 0.737), ('j', 0.613), ('à', 0.486), ('x', 0.427),
+      ('z', 0.326), ('è', 0.271), ('ê', 0.218), ('y', 0.128), ('ç', 0.085), ('k', 0.074),
+      ('û', 0.060), ('ù', 0.058), ('â', 0.051), ('w', 0.049), ('î', 0.045), ('ô', 0.023),
+      ('œ', 0.018), ('ë', 0.008), ('ï', 0.005)];
+
+static SPANISH: &'static [(char, f32)] =
+    &[('e', 12.181), ('a', 11.525), ('o', 8.683), ('s', 7.977), ('r', 6.871), ('n', 6.712),
+      ('i', 6.247), ('d', 5.010), ('l', 4.967), ('t', 4.632), ('c', 4.019), ('m', 3.157),
+      ('u', 2.927), ('p', 2.510), ('b', 2.215), ('g', 1.768), ('v', 1.138), ('y', 1.008),
+      ('q', 0.877), ('ó', 0.827), ('í', 0.725), ('h', 0.703), ('f', 0.692), ('á', 0.502),
+      ('j', 0.493), ('z', 0.467), ('é', 0.433), ('ñ', 0.311), ('x', 0.215), ('ú', 0.168),
+      ('w', 0.017), ('ü', 0.012), ('k', 0.011)];
+
+// The tables above only cover letters.  Give them the same share of spaces
+// and punctuation as the English one has, so the models differ only in
+// their letters.
+fn with_spaces_and_punctuation(letters: &[(char, f32)]) -> ChiSquared {
+    let mut frequencies: Vec<(char, f32)> = letters
+        .iter()
+        .map(|&(c, percent)| (c, percent * 7.9))
+        .collect();
+    frequencies.extend_from_slice(&[(' ', 183.0), ('.', 6.0), (',', 6.0), ('\'', 2.0),
+                                    ('"', 2.0), ('-', 1.0), ('!', 1.0), ('?', 1.0)]);
+    ChiSquared::from_frequencies(&frequencies)
+}
+
+// en, de, fr or es
+pub fn builtin_model(name: &str) -> Option<ChiSquared> {
+    match name {
+        "en" => Some(ChiSquared::english()),
+        "de" => Some(with_spaces_and_punctuation(GERMAN)),
+        "fr" => Some(with_spaces_and_punctuation(FRENCH)),
+        "es" => Some(with_spaces_and_punctuation(SPANISH)),
+        _ => None,
+    }
+}
+
+// A set of models of the same kind, one per language.  As a scorer it rates
+// text by whichever language fits it best, so it can break ciphertexts
+// without being told the language first.
+pub struct Languages<S: Scorer> {
+    models: Vec<(String, S)>,
+}
+
+impl<S: Scorer> Languages<S> {
+    pub fn new() -> Languages<S> {
+        Languages { models: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, model: S) {
+        self.models.push((name.to_string(), model));
+    }
+
+    // the best fitting language and its score
+    pub fn detect(&self, text: &[u8]) -> Option<(&str, f32)> {
+        self.models
+            .iter()
+            .map(|&(ref name, ref model)| (&name[..], model.score(text)))
+            .fold(None, |best, (name, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((name, score)),
+            })
+    }
+}
+
+impl Languages<ChiSquared> {
+    pub fn builtin() -> Languages<ChiSquared> {
+        let mut languages = Languages::new();
+        for name in ["en", "de", "fr", "es"].iter() {
+            languages.add(name, builtin_model(name).unwrap());
+        }
+        languages
+    }
+}
+
+impl Languages<NgramModel> {
+    // n-gram models trained from (name, corpus path) pairs
+    #[allow(dead_code)]
+    pub fn from_files(corpora: &[(&str, &str)],
+                      n: usize)
+                      -> io::Result<Languages<NgramModel>> {
+        let mut languages = Languages::new();
+        for &(name, path) in corpora {
+            languages.add(name, try!(NgramModel::from_file(path, n)));
+        }
+        Ok(languages)
+    }
+}
+
+impl<S: Scorer> Scorer for Languages<S> {
+    fn score(&self, text: &[u8]) -> f32 {
+        self.detect(text).map(|(_, score)| score).unwrap_or(0.0)
+    }
+}
+
+// The distinct words of a corpus, lower case and sorted, for dictionary
+// attacks like challenge 19's.  Anything that isn't a letter separates words.
+pub fn word_list(corpus: &str) -> Vec<String> {
+    let mut words: Vec<String> = corpus
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+// Article 1 of the Universal Declaration of Human Rights, for trying the
+// models out
+pub static SAMPLES: &'static [(&'static str, &'static str)] =
+    &[("en",
+       "All human beings are born free and equal in dignity and rights. They are endowed with \
+        reason and conscience and should act towards one another in a spirit of brotherhood."),
+      ("de",
+       "Alle Menschen sind frei und gleich an Würde und Rechten geboren. Sie sind mit Vernunft \
+        und Gewissen begabt und sollen einander im Geist der Brüderlichkeit begegnen."),
+      ("fr",
+       "Tous les êtres humains naissent libres et égaux en dignité et en droits. Ils sont doués \
+        de raison et de conscience et doivent agir les uns envers les autres dans un esprit de \
+        fraternité."),
+      ("es",
+       "Todos los seres humanos nacen libres e iguales en dignidad y derechos y, dotados como \
+        están de razón y conciencia, deben comportarse fraternalmente los unos con los otros.")];
+
+#[test]
+fn test_detect_language() {
+    use bytes::{most_likely_xor, xor1};
+
+    let languages = Languages::builtin();
+    for &(name, text) in SAMPLES {
+        assert_eq!(languages.detect(text.as_bytes()).unwrap().0, name);
+
+        // and break a single byte xor of it without knowing the language
+        let ciphertext = xor1(text.as_bytes(), 0xa7);
+        let (key, _, _) = most_likely_xor(&ciphertext, &languages).unwrap();
+        assert_eq!(key, 0xa7);
+    }
+}
+
+#[test]
+fn test_trained_languages() {
+    use std::fs::{create_dir, remove_dir_all, File};
+    use std::io::Write;
+    use std::env::temp_dir;
+    use std::process;
+
+    // the samples as corpora, in a directory of this run's own that goes once
+    // the models are built
+    let dir = temp_dir().join(format!("cryptochallenges-corpora-{}", process::id()));
+    create_dir(&dir).unwrap();
+    let mut corpora = Vec::new();
+    for &(name, text) in SAMPLES {
+        let path = dir.join(format!("{}.txt", name));
+        File::create(&path).unwrap().write_all(text.as_bytes()).unwrap();
+        corpora.push((name, path.to_str().unwrap().to_string()));
+    }
+    let paths: Vec<(&str, &str)> = corpora.iter().map(|&(n, ref p)| (n, &p[..])).collect();
+    let languages = Languages::from_files(&paths, 2);
+    remove_dir_all(&dir).unwrap();
+    let languages = languages.unwrap();
+
+    // phrases that aren't in any of the samples
+    assert_eq!(languages.detect(b"the weather is nicer than yesterday").unwrap().0, "en");
+    assert_eq!(languages.detect(b"die kinder gehen nicht in die schule").unwrap().0, "de");
+    assert_eq!(languages.detect(b"nous avons une maison avec un jardin").unwrap().0, "fr");
+    assert_eq!(languages.detect(b"el perro come la comida de la casa").unwrap().0, "es");
+    assert!(Languages::from_files(&[("xx", "no such file")], 2).is_err());
+
+    assert_eq!(word_list("Die Würde, die WÜRDE... und"), vec!["die", "und", "würde"]);
+}
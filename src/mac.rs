@@ -0,0 +1,129 @@
+use aes::{encrypt_cbc, encrypt_cbc_with};
+use aescore::Aes;
+use blockcipher::{BlockCipher, CipherError};
+use bytes::inplace_xor;
+
+// CBC-MAC and CMAC.  A CBC-MAC is just the last block of the CBC encryption of
+// the message, so it's only safe for fixed length messages with a fixed IV -
+// challenges 49 and 50 break it when either of those doesn't hold.  CMAC (RFC
+// 4493) fixes the length extension by mixing a key-derived subkey into the
+// last block.
+
+// The message must be a whole number of blocks - pad it first.
+pub fn cbc_mac(message: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CipherError> {
+    if message.is_empty() || message.len() % 16 != 0 {
+        return Err(CipherError::InvalidLength);
+    }
+    let ciphertext = try!(encrypt_cbc(message, key, iv));
+    Ok(ciphertext[ciphertext.len() - 16..].to_vec())
+}
+
+#[allow(dead_code)]
+pub fn cmac(message: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    cmac_with(&try!(Aes::new(key)), message)
+}
+
+// compare every byte, rather than stopping at the first difference
+pub fn verify_mac(expected: &[u8], mac: &[u8]) -> bool {
+    expected.len() == mac.len() &&
+    expected
+        .iter()
+        .zip(mac)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// multiply by x in GF(2^n), with the big endian bit order CMAC uses
+#[allow(dead_code)]
+pub fn double(block: &[u8], rb: u8) -> Vec<u8> {
+    let mut doubled = vec![0u8; block.len()];
+    for i in 0..block.len() {
+        let carry = if i + 1 < block.len() { block[i + 1] >> 7 } else { 0 };
+        doubled[i] = (block[i] << 1) | carry;
+    }
+    if block[0] & 0x80 != 0 {
+        let last = doubled.len() - 1;
+        doubled[last] ^= rb;
+    }
+    doubled
+}
+
+// K1 and K2 from RFC 4493 section 2.3, for 64 or 128 bit blocks
+#[allow(dead_code)]
+pub fn cmac_subkeys<C: BlockCipher>(cipher: &C) -> Result<(Vec<u8>, Vec<u8>), CipherError> {
+    let rb = match cipher.block_size() {
+        8 => 0x1b,
+        16 => 0x87,
+        _ => return Err(CipherError::InvalidLength),
+    };
+    let mut l = vec![0u8; cipher.block_size()];
+    cipher.encrypt_block(&mut l);
+    let k1 = double(&l, rb);
+    let k2 = double(&k1, rb);
+    Ok((k1, k2))
+}
+
+#[allow(dead_code)]
+pub fn cmac_with<C: BlockCipher>(cipher: &C, message: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let block_size = cipher.block_size();
+    let (k1, k2) = try!(cmac_subkeys(cipher));
+
+    // A complete last block gets K1 xored in.  Anything else is padded with a
+    // one bit and zeros and gets K2.
+    let last_start = if message.is_empty() {
+        0
+    } else {
+        (message.len() - 1) / block_size * block_size
+    };
+    let mut last_block = message[last_start..].to_vec();
+    if last_block.len() == block_size {
+        last_block = inplace_xor(last_block, &k1);
+    } else {
+        last_block.push(0x80);
+        last_block.resize(block_size, 0);
+        last_block = inplace_xor(last_block, &k2);
+    }
+
+    let mut padded = message[..last_start].to_vec();
+    padded.extend(last_block);
+    let ciphertext = try!(encrypt_cbc_with(cipher, &padded, &vec![0u8; block_size]));
+    Ok(ciphertext[ciphertext.len() - block_size..].to_vec())
+}
+
+#[test]
+fn test_cmac() {
+    use hexstring::{fromhex, tohex};
+
+    // RFC 4493 section 4
+    let key = fromhex("2b7e151628aed2a6abf7158809cf4f3c").unwrap();
+    let message = fromhex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                           30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710")
+            .unwrap();
+    let (k1, k2) = cmac_subkeys(&Aes::new(&key).unwrap()).unwrap();
+    assert_eq!(tohex(&k1), "fbeed618357133667c85e08f7236a8de");
+    assert_eq!(tohex(&k2), "f7ddac306ae266ccf90bc11ee46d513b");
+
+    let expected = [(0, "bb1d6929e95937287fa37d129b756746"),
+                    (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+                    (40, "dfa66747de9ae63030ca32611497c827"),
+                    (64, "51f0bebf7e3b9d92fc49741779363cfe")];
+    for &(len, mac) in expected.iter() {
+        assert_eq!(tohex(&cmac(&message[..len], &key).unwrap()), mac);
+    }
+}
+
+#[test]
+fn test_cbc_mac() {
+    use bytes::pad;
+    use hexstring::tohex;
+
+    // the example from challenge 50
+    let message = pad(b"alert('MZA who was that?');\n".to_vec(), 16);
+    assert_eq!(tohex(&cbc_mac(&message, b"YELLOW SUBMARINE", &[0u8; 16]).unwrap()),
+               "296b8d7cb78a243dda4d0a61d33bbdd1");
+    assert_eq!(cbc_mac(b"not a block", b"YELLOW SUBMARINE", &[0u8; 16]),
+               Err(CipherError::InvalidLength));
+
+    assert!(verify_mac(b"1234", b"1234"));
+    assert!(!verify_mac(b"1234", b"1235"));
+    assert!(!verify_mac(b"1234", b"123"));
+}
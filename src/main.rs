@@ -1,6 +1,33 @@
 mod bytes;
+mod scoring;
+mod language;
+mod formats;
+mod keylength;
+mod padding;
 mod hexstring;
+mod blockcipher;
+mod aescore;
+mod aesni;
+mod bitslice;
+mod des;
+mod rc4;
+mod salsa20;
+mod poly1305;
+mod chacha20;
+mod keystream;
+mod fixednonce;
 mod aes;
+mod stream;
+mod gcm;
+mod mac;
+mod keywrap;
+mod xts;
+mod siv;
+mod errorpropagation;
+mod benchmark;
+mod timingvariance;
+mod multilanguage;
+mod formatdetection;
 mod challenge3;
 mod challenge4;
 mod challenge6;
@@ -19,9 +46,12 @@ mod challenge18;
 mod challenge19;
 mod challenge20;
 mod challenge21;
+mod challenge25;
+mod challenge49;
+mod challenge50;
+mod challenge56;
 
 extern crate base64;
-extern crate crypto;
 extern crate rand;
 #[macro_use]
 extern crate lazy_static;
@@ -47,6 +77,15 @@ macro_rules! challenge {
         });
 }
 
+// The same, but only when named on the command line.  These are demos rather
+// than challenges, and some take a while or print timings.
+macro_rules! demo {
+    ($x:ident) => (
+        if env::args().nth(1).map_or(false, |name| name == stringify!($x)) {
+            challenge!($x);
+        });
+}
+
 fn main() {
     challenge!(challenge3);
     challenge!(challenge4);
@@ -66,4 +105,13 @@ fn main() {
     challenge!(challenge19);
     challenge!(challenge20);
     challenge!(challenge21);
+    challenge!(challenge25);
+    challenge!(challenge49);
+    challenge!(challenge50);
+    challenge!(challenge56);
+    demo!(errorpropagation);
+    demo!(benchmark);
+    demo!(timingvariance);
+    demo!(multilanguage);
+    demo!(formatdetection);
 }
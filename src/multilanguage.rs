@@ -0,0 +1,19 @@
+use bytes::{most_likely_xor, rand_u8, xor1};
+use language::{Languages, SAMPLES};
+
+// Not one of the challenges - challenge 3 in four languages.  Each sample is
+// xored with a random byte and broken with the built-in language models,
+// which don't need to be told which language to expect.
+pub fn multilanguage() {
+    let languages = Languages::builtin();
+    for &(name, text) in SAMPLES {
+        let ciphertext = xor1(text.as_bytes(), rand_u8());
+        let (key, _, cleartext) = most_likely_xor(&ciphertext, &languages).unwrap();
+        let (detected, _) = languages.detect(&cleartext).unwrap();
+        println!("{} (really {}), key {}: {}",
+                 detected,
+                 name,
+                 key,
+                 String::from_utf8_lossy(&cleartext));
+    }
+}
@@ -0,0 +1,236 @@
+use bytes::{pad, random_bytes, strip_padding, PaddingError};
+
+// A block cipher padding scheme.  CBC ciphertext stealing is the other common
+// way of dealing with partial blocks, but it rearranges the ciphertext rather
+// than padding the cleartext, so it lives in aes.rs as its own mode.
+pub trait Padding {
+    // fill out the last block (always adding at least one byte, except for
+    // zero padding)
+    fn pad(&self, bytes: Vec<u8>, blocksize: usize) -> Vec<u8>;
+
+    // check and remove the padding
+    fn unpad(&self, bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError>;
+
+    fn is_valid(&self, bytes: &[u8], blocksize: usize) -> bool {
+        self.unpad(bytes.to_vec(), blocksize).is_ok()
+    }
+
+    // What the last n bytes of a block can look like when they're valid
+    // padding of length n.  Any random filler is zeros.  This is what a
+    // padding oracle attack aims for.
+    fn padding_bytes(&self, n: usize, blocksize: usize) -> Vec<u8>;
+}
+
+#[allow(dead_code)]
+fn check_length(bytes: &[u8], blocksize: usize) -> Result<(), PaddingError> {
+    if bytes.is_empty() {
+        Err(PaddingError::Empty)
+    } else if bytes.len() % blocksize != 0 {
+        Err(PaddingError::NotBlockAligned)
+    } else {
+        Ok(())
+    }
+}
+
+// check the length byte at the end is possible, returning it
+#[allow(dead_code)]
+fn check_length_byte(bytes: &[u8], blocksize: usize) -> Result<usize, PaddingError> {
+    try!(check_length(bytes, blocksize));
+    let n = bytes[bytes.len() - 1];
+    if n == 0 || n as usize > blocksize {
+        Err(PaddingError::BadPaddingLength(n))
+    } else {
+        Ok(n as usize)
+    }
+}
+
+#[allow(dead_code)]
+fn padding_len(bytes: &[u8], blocksize: usize) -> usize {
+    blocksize - (bytes.len() % blocksize)
+}
+
+// n copies of n
+pub struct Pkcs7;
+
+impl Padding for Pkcs7 {
+    fn pad(&self, bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
+        pad(bytes, blocksize)
+    }
+
+    fn unpad(&self, bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError> {
+        strip_padding(bytes, blocksize)
+    }
+
+    fn padding_bytes(&self, n: usize, _: usize) -> Vec<u8> {
+        vec![n as u8; n]
+    }
+}
+
+// zeros, then the number of padding bytes
+#[allow(dead_code)]
+pub struct AnsiX923;
+
+impl Padding for AnsiX923 {
+    fn pad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
+        let n = padding_len(&bytes, blocksize);
+        let new_len = bytes.len() + n - 1;
+        bytes.resize(new_len, 0);
+        bytes.push(n as u8);
+        bytes
+    }
+
+    fn unpad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError> {
+        let n = try!(check_length_byte(&bytes, blocksize));
+        let len = bytes.len();
+        if bytes[(len - n)..(len - 1)].iter().any(|b| *b != 0) {
+            return Err(PaddingError::BadPaddingByte);
+        }
+        bytes.truncate(len - n);
+        Ok(bytes)
+    }
+
+    fn padding_bytes(&self, n: usize, _: usize) -> Vec<u8> {
+        let mut padding = vec![0u8; n - 1];
+        padding.push(n as u8);
+        padding
+    }
+}
+
+// random bytes, then the number of padding bytes.  Only the last byte can be
+// checked.
+#[allow(dead_code)]
+pub struct Iso10126;
+
+impl Padding for Iso10126 {
+    fn pad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
+        let n = padding_len(&bytes, blocksize);
+        bytes.extend(random_bytes(n - 1));
+        bytes.push(n as u8);
+        bytes
+    }
+
+    fn unpad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError> {
+        let n = try!(check_length_byte(&bytes, blocksize));
+        let new_len = bytes.len() - n;
+        bytes.truncate(new_len);
+        Ok(bytes)
+    }
+
+    fn padding_bytes(&self, n: usize, _: usize) -> Vec<u8> {
+        let mut padding = vec![0u8; n - 1];
+        padding.push(n as u8);
+        padding
+    }
+}
+
+// a single 0x80 (a one bit), then zeros
+#[allow(dead_code)]
+pub struct Iso7816;
+
+impl Padding for Iso7816 {
+    fn pad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
+        let n = padding_len(&bytes, blocksize);
+        bytes.push(0x80);
+        let new_len = bytes.len() + n - 1;
+        bytes.resize(new_len, 0);
+        bytes
+    }
+
+    fn unpad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError> {
+        try!(check_length(&bytes, blocksize));
+
+        // the 0x80 has to be somewhere in the last block
+        let last_block_start = bytes.len() - blocksize;
+        while bytes.len() > last_block_start {
+            match bytes.pop() {
+                Some(0) => continue,
+                Some(0x80) => return Ok(bytes),
+                _ => break,
+            }
+        }
+        Err(PaddingError::BadPaddingByte)
+    }
+
+    fn padding_bytes(&self, n: usize, _: usize) -> Vec<u8> {
+        let mut padding = vec![0x80];
+        padding.resize(n, 0);
+        padding
+    }
+}
+
+// Zeros, and only if the data doesn't already fill the last block.  Trailing
+// zeros in the data itself are lost, and there's nothing to check.
+#[allow(dead_code)]
+pub struct ZeroPadding;
+
+impl Padding for ZeroPadding {
+    fn pad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Vec<u8> {
+        let new_len = (bytes.len() + blocksize - 1) / blocksize * blocksize;
+        bytes.resize(new_len, 0);
+        bytes
+    }
+
+    fn unpad(&self, mut bytes: Vec<u8>, blocksize: usize) -> Result<Vec<u8>, PaddingError> {
+        if bytes.len() % blocksize != 0 {
+            return Err(PaddingError::NotBlockAligned);
+        }
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        Ok(bytes)
+    }
+
+    fn padding_bytes(&self, n: usize, _: usize) -> Vec<u8> {
+        vec![0u8; n]
+    }
+}
+
+#[cfg(test)]
+fn check_round_trip<P: Padding>(padding: &P) {
+    for blocksize in [8, 16].iter() {
+        for len in 0..40 {
+            let bytes = random_bytes(len);
+            let padded = padding.pad(bytes.clone(), *blocksize);
+            assert_eq!(padded.len() % blocksize, 0);
+            assert!(padded.len() > len);
+            assert!(padding.is_valid(&padded, *blocksize));
+            assert_eq!(padding.unpad(padded, *blocksize), Ok(bytes));
+        }
+    }
+}
+
+#[test]
+fn test_padding_schemes() {
+    check_round_trip(&Pkcs7);
+    check_round_trip(&AnsiX923);
+    check_round_trip(&Iso10126);
+    check_round_trip(&Iso7816);
+
+    assert_eq!(AnsiX923.pad(vec![1, 2, 3], 8), [1, 2, 3, 0, 0, 0, 0, 5]);
+    assert_eq!(Iso7816.pad(vec![1, 2, 3], 8), [1, 2, 3, 0x80, 0, 0, 0, 0]);
+    assert_eq!(Iso7816.pad(vec![1, 2, 3, 4, 5, 6, 7], 8), [1, 2, 3, 4, 5, 6, 7, 0x80]);
+    assert_eq!(Iso10126.pad(vec![1, 2, 3], 8)[7], 5);
+    assert_eq!(ZeroPadding.pad(vec![1, 2, 3], 4), [1, 2, 3, 0]);
+    assert_eq!(ZeroPadding.pad(vec![1, 2, 3, 4], 4), [1, 2, 3, 4]);
+    assert_eq!(ZeroPadding.unpad(vec![1, 2, 0, 0], 4), Ok(vec![1, 2]));
+
+    assert_eq!(AnsiX923.unpad(vec![1, 2, 3, 4, 1, 0, 0, 4], 8),
+               Err(PaddingError::BadPaddingByte));
+    assert_eq!(Iso10126.unpad(vec![1, 2, 3, 4, 1, 0, 0, 4], 8),
+               Ok(vec![1, 2, 3, 4]));
+    assert_eq!(Iso7816.unpad(vec![1, 2, 3, 4, 0, 0, 0, 0], 8),
+               Err(PaddingError::BadPaddingByte));
+    assert_eq!(Iso7816.unpad(vec![0x80, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0], 8),
+               Err(PaddingError::BadPaddingByte));
+    assert_eq!(Iso7816.unpad(vec![], 8), Err(PaddingError::Empty));
+
+    for n in 1..9 {
+        for padding_bytes in [Pkcs7.padding_bytes(n, 8),
+                              AnsiX923.padding_bytes(n, 8),
+                              Iso7816.padding_bytes(n, 8)]
+                    .iter() {
+            assert_eq!(padding_bytes.len(), n);
+        }
+        assert!(Iso7816.is_valid(&Iso7816.padding_bytes(n, 8), n));
+    }
+}
@@ -0,0 +1,132 @@
+// Poly1305 (RFC 8439 section 2.5).  The message is split into 16 byte
+// chunks, each with a 1 appended, and used as the coefficients of a
+// polynomial evaluated at r modulo 2^130 - 5; s is added to the result.  The
+// key (r, s) must only ever be used for one message.
+//
+// The arithmetic is done in five 26 bit limbs so the products fit in a u64,
+// in the style of poly1305-donna.
+
+#[allow(dead_code)]
+pub const TAG_SIZE: usize = 16;
+
+#[allow(dead_code)]
+const MASK: u64 = 0x3ffffff;
+
+#[allow(dead_code)]
+fn le_word(bytes: &[u8]) -> u64 {
+    bytes[..4].iter().rev().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+#[allow(dead_code)]
+pub fn poly1305(message: &[u8], key: &[u8; 32]) -> [u8; TAG_SIZE] {
+    // r, clamped
+    let r = [le_word(&key[0..]) & 0x3ffffff,
+             (le_word(&key[3..]) >> 2) & 0x3ffff03,
+             (le_word(&key[6..]) >> 4) & 0x3ffc0ff,
+             (le_word(&key[9..]) >> 6) & 0x3f03fff,
+             (le_word(&key[12..]) >> 8) & 0x00fffff];
+    // multiplying by 2^130 is multiplying by 5, so the limbs that overflow
+    // the top come back round times 5
+    let s = [r[1] * 5, r[2] * 5, r[3] * 5, r[4] * 5];
+    let mut h = [0u64; 5];
+
+    for chunk in message.chunks(16) {
+        let mut block = [0u8; 17];
+        block[..chunk.len()].copy_from_slice(chunk);
+        block[chunk.len()] = 1;
+
+        h[0] += le_word(&block[0..]) & MASK;
+        h[1] += (le_word(&block[3..]) >> 2) & MASK;
+        h[2] += (le_word(&block[6..]) >> 4) & MASK;
+        h[3] += (le_word(&block[9..]) >> 6) & MASK;
+        h[4] += (le_word(&block[12..]) >> 8) | (block[16] as u64) << 24;
+
+        let d = [h[0] * r[0] + h[1] * s[3] + h[2] * s[2] + h[3] * s[1] + h[4] * s[0],
+                 h[0] * r[1] + h[1] * r[0] + h[2] * s[3] + h[3] * s[2] + h[4] * s[1],
+                 h[0] * r[2] + h[1] * r[1] + h[2] * r[0] + h[3] * s[3] + h[4] * s[2],
+                 h[0] * r[3] + h[1] * r[2] + h[2] * r[1] + h[3] * r[0] + h[4] * s[3],
+                 h[0] * r[4] + h[1] * r[3] + h[2] * r[2] + h[3] * r[1] + h[4] * r[0]];
+
+        // partial reduction - h ends up a little over 130 bits at most
+        let mut carry = 0;
+        for i in 0..5 {
+            let limb = d[i] + carry;
+            h[i] = limb & MASK;
+            carry = limb >> 26;
+        }
+        h[0] += carry * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= MASK;
+    }
+
+    // full carry, then subtract p if h >= p (by checking whether h + 5
+    // reaches 2^130), without branching on h
+    let mut carry = 0;
+    for i in 1..5 {
+        h[i] += carry;
+        carry = h[i] >> 26;
+        h[i] &= MASK;
+    }
+    h[0] += carry * 5;
+    carry = h[0] >> 26;
+    h[0] &= MASK;
+    h[1] += carry;
+
+    let mut g = [0u64; 5];
+    carry = 5;
+    for i in 0..5 {
+        let limb = h[i] + carry;
+        g[i] = limb & MASK;
+        carry = limb >> 26;
+    }
+    // carry is 1 if h + 5 >= 2^130, in which case use g
+    let use_g = 0u64.wrapping_sub(carry);
+    for i in 0..5 {
+        h[i] = (h[i] & !use_g) | (g[i] & use_g);
+    }
+
+    // h mod 2^128, plus s
+    let low = h[0] | h[1] << 26 | h[2] << 52;
+    let high = h[2] >> 12 | h[3] << 14 | h[4] << 40;
+    let h = low as u128 | (high as u128) << 64;
+    let s = key[16..].iter().rev().fold(0u128, |acc, b| (acc << 8) | *b as u128);
+    let tag = h.wrapping_add(s);
+
+    let mut output = [0u8; TAG_SIZE];
+    for (i, b) in output.iter_mut().enumerate() {
+        *b = (tag >> (8 * i)) as u8;
+    }
+    output
+}
+
+#[cfg(test)]
+fn poly1305_vector(key: &str, message: &str, tag: &str) {
+    use hexstring::{fromhex, tohex};
+
+    let mut key_bytes = [0u8; 32];
+    key_bytes.copy_from_slice(&fromhex(key).unwrap());
+    assert_eq!(tohex(&poly1305(&fromhex(message).unwrap(), &key_bytes)), tag);
+}
+
+#[test]
+fn test_poly1305() {
+    use hexstring::tohex;
+
+    // RFC 8439 2.5.2
+    poly1305_vector("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b",
+                    &tohex(b"Cryptographic Forum Research Group"),
+                    "a8061dc1305136c6c22b8baf0c0127a9");
+
+    // RFC 8439 A.3 #5, #6 and #8 - the edge cases of the final reduction
+    poly1305_vector("0200000000000000000000000000000000000000000000000000000000000000",
+                    "ffffffffffffffffffffffffffffffff",
+                    "03000000000000000000000000000000");
+    poly1305_vector("02000000000000000000000000000000ffffffffffffffffffffffffffffffff",
+                    "02000000000000000000000000000000",
+                    "03000000000000000000000000000000");
+    poly1305_vector("0100000000000000000000000000000000000000000000000000000000000000",
+                    "ffffffffffffffffffffffffffffffff\
+                     fbfefefefefefefefefefefefefefefe\
+                     01010101010101010101010101010101",
+                    "00000000000000000000000000000000");
+}
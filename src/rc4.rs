@@ -0,0 +1,77 @@
+use blockcipher::CipherError;
+
+// RC4.  The key scheduling (KSA) shuffles a permutation of 0..256 with the
+// key, and the output generator (PRGA) keeps swapping entries and putting out
+// one of them per byte.  Its early output bytes are noticeably biased, which
+// is what challenge 56 exploits.
+pub struct Rc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Rc4 {
+    // keys can be 1 to 256 bytes
+    pub fn new(key: &[u8]) -> Result<Rc4, CipherError> {
+        if key.is_empty() || key.len() > 256 {
+            return Err(CipherError::InvalidKeyLength);
+        }
+
+        let mut state = [0u8; 256];
+        for (i, s) in state.iter_mut().enumerate() {
+            *s = i as u8;
+        }
+        let mut j = 0u8;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+
+        Ok(Rc4 {
+               state: state,
+               i: 0,
+               j: 0,
+           })
+    }
+
+    pub fn next_byte(&mut self) -> u8 {
+        self.i = self.i.wrapping_add(1);
+        self.j = self.j.wrapping_add(self.state[self.i as usize]);
+        self.state.swap(self.i as usize, self.j as usize);
+        let k = self.state[self.i as usize].wrapping_add(self.state[self.j as usize]);
+        self.state[k as usize]
+    }
+
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut() {
+            *b ^= self.next_byte();
+        }
+    }
+}
+
+// encrypts or decrypts
+pub fn rc4(data: &[u8], key: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let mut output = data.to_vec();
+    try!(Rc4::new(key)).apply_keystream(&mut output);
+    Ok(output)
+}
+
+#[test]
+fn test_rc4() {
+    use hexstring::tohex;
+
+    // RFC 6229, keystream at offsets 0 and 16 for two of the keys
+    let mut cipher = Rc4::new(&[1, 2, 3, 4, 5]).unwrap();
+    let mut keystream = [0u8; 32];
+    cipher.apply_keystream(&mut keystream);
+    assert_eq!(tohex(&keystream),
+               "b2396305f03dc027ccc3524a0a1118a86982944f18fc82d589c403a47a0d0919");
+
+    let key: Vec<u8> = (1..17).collect();
+    assert_eq!(tohex(&rc4(&[0u8; 16], &key).unwrap()),
+               "9ac7cc9a609d1ef7b2932899cde41b97");
+
+    // the classic Wikipedia example
+    assert_eq!(tohex(&rc4(b"Plaintext", b"Key").unwrap()), "bbf316e8d940af0ad3");
+    assert!(Rc4::new(&[]).is_err());
+}
@@ -0,0 +1,157 @@
+use blockcipher::CipherError;
+
+// Salsa20 (Bernstein's spec, "Salsa20 specification").  A 64 byte block of
+// keystream is the hash of a 4x4 matrix of words holding constants, the key,
+// an 8 byte nonce and a 64 bit block counter.  16 byte keys are used twice
+// with a different constant.
+
+pub const BLOCK_SIZE: usize = 64;
+
+fn le_word(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0u32, |acc, b| (acc << 8) | *b as u32)
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+    x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+    x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+    x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+}
+
+// the Salsa20 hash - ten double rounds, then the input added back in
+pub fn salsa20_core(input: &[u32; 16]) -> [u8; BLOCK_SIZE] {
+    let mut x = *input;
+    for _ in 0..10 {
+        // columns
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 5, 9, 13, 1);
+        quarter_round(&mut x, 10, 14, 2, 6);
+        quarter_round(&mut x, 15, 3, 7, 11);
+        // rows
+        quarter_round(&mut x, 0, 1, 2, 3);
+        quarter_round(&mut x, 5, 6, 7, 4);
+        quarter_round(&mut x, 10, 11, 8, 9);
+        quarter_round(&mut x, 15, 12, 13, 14);
+    }
+
+    let mut output = [0u8; BLOCK_SIZE];
+    for (i, chunk) in output.chunks_mut(4).enumerate() {
+        let word = x[i].wrapping_add(input[i]);
+        for (j, b) in chunk.iter_mut().enumerate() {
+            *b = (word >> (8 * j)) as u8;
+        }
+    }
+    output
+}
+
+pub struct Salsa20 {
+    state: [u32; 16],
+    keystream: [u8; BLOCK_SIZE],
+    keystream_pos: usize,
+}
+
+impl Salsa20 {
+    // 16 or 32 byte keys, 8 byte nonces
+    pub fn new(key: &[u8], nonce: &[u8]) -> Result<Salsa20, CipherError> {
+        let constant: &[u8] = match key.len() {
+            16 => b"expand 16-byte k",
+            32 => b"expand 32-byte k",
+            _ => return Err(CipherError::InvalidKeyLength),
+        };
+        if nonce.len() != 8 {
+            return Err(CipherError::InvalidLength);
+        }
+        let (k0, k1) = key.split_at(16);
+        let k1 = if k1.is_empty() { k0 } else { k1 };
+
+        let mut state = [0u32; 16];
+        for i in 0..4 {
+            state[i * 5] = le_word(&constant[i * 4..i * 4 + 4]);
+            state[1 + i] = le_word(&k0[i * 4..i * 4 + 4]);
+            state[11 + i] = le_word(&k1[i * 4..i * 4 + 4]);
+        }
+        state[6] = le_word(&nonce[..4]);
+        state[7] = le_word(&nonce[4..]);
+
+        Ok(Salsa20 {
+               state: state,
+               keystream: [0u8; BLOCK_SIZE],
+               keystream_pos: BLOCK_SIZE,
+           })
+    }
+
+    fn next_block(&mut self) {
+        self.keystream = salsa20_core(&self.state);
+        self.keystream_pos = 0;
+        let counter = ((self.state[9] as u64) << 32 | self.state[8] as u64).wrapping_add(1);
+        self.state[8] = counter as u32;
+        self.state[9] = (counter >> 32) as u32;
+    }
+
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut() {
+            if self.keystream_pos == BLOCK_SIZE {
+                self.next_block();
+            }
+            *b ^= self.keystream[self.keystream_pos];
+            self.keystream_pos += 1;
+        }
+    }
+}
+
+// encrypts or decrypts, starting from block 0
+#[allow(dead_code)]
+pub fn salsa20(data: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let mut output = data.to_vec();
+    try!(Salsa20::new(key, nonce)).apply_keystream(&mut output);
+    Ok(output)
+}
+
+#[test]
+fn test_salsa20_core() {
+    // the Salsa20 spec's example of the expansion function, section 9: key
+    // 1..16 and 201..216, nonce and counter 101..116
+    let key: Vec<u8> = (1..17).chain(201..217).collect();
+    let input: Vec<u8> = (101..117).collect();
+    let mut cipher = Salsa20::new(&key, &input[..8]).unwrap();
+    cipher.state[8] = le_word(&input[8..12]);
+    cipher.state[9] = le_word(&input[12..]);
+    let expected: Vec<u8> = vec![69, 37, 68, 39, 41, 15, 107, 193, 255, 139, 122, 6, 170, 233,
+                                 217, 98, 89, 144, 182, 106, 21, 51, 200, 65, 239, 49, 222, 34,
+                                 215, 114, 40, 126, 104, 197, 7, 225, 197, 153, 31, 2, 102, 78,
+                                 76, 176, 84, 245, 246, 184, 177, 160, 133, 130, 6, 72, 149, 119,
+                                 192, 195, 132, 236, 234, 103, 246, 74];
+    assert_eq!(salsa20_core(&cipher.state).to_vec(), expected);
+}
+
+#[test]
+fn test_salsa20() {
+    use bytes::random_bytes;
+    use hexstring::tohex;
+
+    // eSTREAM test vectors, set 1 vector 0, 128 and 256 bit keys
+    let mut key = [0u8; 32];
+    key[0] = 0x80;
+    let keystream = salsa20(&[0u8; 64], &key[..16], &[0u8; 8]).unwrap();
+    assert_eq!(tohex(&keystream),
+               "4dfa5e481da23ea09a31022050859936da52fcee218005164f267cb65f5cfd7f\
+                2b4f97e0ff16924a52df269515110a07f9e460bc65ef95da58f740b7d1dbb0aa");
+    let keystream = salsa20(&[0u8; 64], &key, &[0u8; 8]).unwrap();
+    assert_eq!(tohex(&keystream),
+               "e3be8fdd8beca2e3ea8ef9475b29a6e7003951e1097a5c38d23b7a5fad9f6844\
+                b22c97559e2723c7cbbd3fe4fc8d9a0744652a83e72a9c461876af4d7ef1a117");
+
+    // pieces of any size continue the same keystream
+    let key = random_bytes(32);
+    let cleartext = random_bytes(300);
+    let ciphertext = salsa20(&cleartext, &key, &[7u8; 8]).unwrap();
+    let mut cipher = Salsa20::new(&key, &[7u8; 8]).unwrap();
+    let mut pieces = cleartext.clone();
+    for chunk in pieces.chunks_mut(23) {
+        cipher.apply_keystream(chunk);
+    }
+    assert_eq!(pieces, ciphertext);
+
+    assert!(Salsa20::new(&[0u8; 24], &[0u8; 8]).is_err());
+    assert!(Salsa20::new(&[0u8; 32], &[0u8; 12]).is_err());
+}
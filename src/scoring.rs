@@ -0,0 +1,245 @@
+use bytes::englishness;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+
+// Ways of rating how much a candidate cleartext looks like natural language,
+// for picking the right key out of a brute force.  Scores are only comparable
+// between candidates of the same length rated by the same scorer.
+
+pub trait Scorer {
+    // higher is more likely to be the real cleartext
+    fn score(&self, text: &[u8]) -> f32;
+}
+
+// bytes::englishness - a hand-picked weight per character class
+#[allow(dead_code)]
+pub struct Heuristic;
+
+impl Scorer for Heuristic {
+    fn score(&self, text: &[u8]) -> f32 {
+        englishness(text)
+    }
+}
+
+// The models ignore case and treat all whitespace as a space, so a word list
+// with one word per line trains them as well as running text does.
+fn normalize(b: u8) -> u8 {
+    match b {
+        b'\n' | b'\r' | b'\t' => b' ',
+        _ => b.to_ascii_lowercase(),
+    }
+}
+
+#[allow(dead_code)]
+fn read_corpus(path: &str) -> io::Result<Vec<u8>> {
+    let mut corpus = Vec::new();
+    try!(try!(File::open(path)).read_to_end(&mut corpus));
+    Ok(corpus)
+}
+
+// Pearson's chi-squared statistic of the byte counts against the frequencies
+// expected of the language, per byte of text so that texts of different lengths
+// can be compared, and negated so that a closer fit scores higher.  Every
+// byte value is counted, so unprintable bytes - which the model gives almost
+// no chance - are heavily penalised.
+pub struct ChiSquared {
+    expected: [f32; 256],
+}
+
+// letter frequencies of English text in parts per thousand, spaces and a
+// little punctuation included
+static ENGLISH_FREQUENCIES: &'static [(char, f32)] =
+    &[(' ', 183.0), ('e', 102.0), ('t', 75.0), ('a', 65.0), ('o', 62.0), ('i', 57.0),
+      ('n', 57.0), ('s', 53.0), ('h', 50.0), ('r', 50.0), ('d', 33.0), ('l', 33.0), ('u', 23.0),
+      ('c', 22.0), ('m', 20.0), ('w', 19.0), ('f', 18.0), ('g', 16.0), ('y', 16.0), ('p', 15.0),
+      ('b', 12.0), ('v', 8.0), ('k', 6.0), ('.', 6.0), (',', 6.0), ('\'', 2.0), ('"', 2.0),
+      ('j', 1.0), ('x', 1.0), ('q', 1.0), ('z', 1.0), ('-', 1.0), ('!', 1.0), ('?', 1.0)];
+
+impl ChiSquared {
+    // Anything not in the table gets a small share rather than nothing, so a
+    // single stray byte doesn't make the statistic infinite.
+    fn from_counts(counts: &[f32; 256]) -> ChiSquared {
+        let smoothing = 0.01;
+        let total: f32 = counts.iter().map(|c| c + smoothing).sum();
+        let mut expected = [0f32; 256];
+        for (e, c) in expected.iter_mut().zip(counts.iter()) {
+            *e = (c + smoothing) / total;
+        }
+        ChiSquared { expected: expected }
+    }
+
+    // From a table of relative character frequencies.  The model is of bytes,
+    // so a character outside ASCII counts towards each byte of its UTF-8
+    // encoding.
+    pub fn from_frequencies(frequencies: &[(char, f32)]) -> ChiSquared {
+        let mut counts = [0f32; 256];
+        for &(c, frequency) in frequencies {
+            let mut utf8 = [0u8; 4];
+            for b in c.encode_utf8(&mut utf8).bytes() {
+                counts[b as usize] += frequency;
+            }
+        }
+        ChiSquared::from_counts(&counts)
+    }
+
+    pub fn english() -> ChiSquared {
+        ChiSquared::from_frequencies(ENGLISH_FREQUENCIES)
+    }
+
+    #[allow(dead_code)]
+    pub fn from_corpus(corpus: &[u8]) -> ChiSquared {
+        let mut counts = [0f32; 256];
+        for b in corpus {
+            counts[normalize(*b) as usize] += 1.0;
+        }
+        ChiSquared::from_counts(&counts)
+    }
+
+    #[allow(dead_code)]
+    pub fn from_file(path: &str) -> io::Result<ChiSquared> {
+        Ok(ChiSquared::from_corpus(&try!(read_corpus(path))))
+    }
+}
+
+impl Scorer for ChiSquared {
+    fn score(&self, text: &[u8]) -> f32 {
+        let mut observed = [0f32; 256];
+        for b in text {
+            observed[normalize(*b) as usize] += 1.0;
+        }
+        if text.is_empty() {
+            return 0.0;
+        }
+        let len = text.len() as f32;
+        let chi_squared: f32 = observed
+            .iter()
+            .zip(self.expected.iter())
+            .map(|(o, p)| (o - p * len).powi(2) / (p * len))
+            .sum();
+        -chi_squared / len
+    }
+}
+
+// The average log probability of each n byte sequence in the text, with the
+// probabilities counted from a corpus.  Sequences the corpus never had get a
+// probability well below anything it did, and far lower again if they hold
+// control characters - a corpus can easily be missing punctuation or accented
+// letters, but text doesn't have those.
+pub struct NgramModel {
+    n: usize,
+    log_probabilities: HashMap<Vec<u8>, f32>,
+    unseen: f32,
+    unprintable: f32,
+}
+
+// bytes past ASCII count - they're how UTF-8 spells accented letters
+fn is_printable(b: u8) -> bool {
+    b >= 0x20 && b != 0x7f
+}
+
+impl NgramModel {
+    pub fn from_corpus(corpus: &[u8], n: usize) -> NgramModel {
+        assert!(n > 0);
+        let normalized: Vec<u8> = corpus.iter().map(|b| normalize(*b)).collect();
+        let mut counts = HashMap::new();
+        for ngram in normalized.windows(n) {
+            *counts.entry(ngram.to_vec()).or_insert(0u32) += 1;
+        }
+
+        let total = normalized.len().saturating_sub(n - 1).max(1) as f32;
+        NgramModel {
+            n: n,
+            log_probabilities: counts
+                .into_iter()
+                .map(|(ngram, count)| (ngram, (count as f32 / total).ln()))
+                .collect(),
+            unseen: (0.01 / total).ln(),
+            unprintable: (0.0001 / total).ln(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn from_file(path: &str, n: usize) -> io::Result<NgramModel> {
+        Ok(NgramModel::from_corpus(&try!(read_corpus(path)), n))
+    }
+
+    // trained on the bundled list of common English words
+    pub fn english(n: usize) -> NgramModel {
+        NgramModel::from_corpus(include_bytes!("google-10000-english-usa.txt"), n)
+    }
+}
+
+impl Scorer for NgramModel {
+    fn score(&self, text: &[u8]) -> f32 {
+        if text.len() < self.n {
+            return self.unseen;
+        }
+        let normalized: Vec<u8> = text.iter().map(|b| normalize(*b)).collect();
+        let log_likelihood: f32 = normalized
+            .windows(self.n)
+            .map(|ngram| match self.log_probabilities.get(ngram) {
+                     Some(log_probability) => *log_probability,
+                     None if ngram.iter().all(|b| is_printable(*b)) => self.unseen,
+                     None => self.unprintable,
+                 })
+            .sum();
+        log_likelihood / (text.len() - self.n + 1) as f32
+    }
+}
+
+#[test]
+fn test_scorers() {
+    use bytes::most_likely_xor;
+    use hexstring::fromhex;
+
+    // challenge 3, which every scorer gets
+    let ciphertext = fromhex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736")
+        .unwrap();
+    let scorers: Vec<Box<Scorer>> = vec![Box::new(Heuristic),
+                                         Box::new(ChiSquared::english()),
+                                         Box::new(NgramModel::english(2)),
+                                         Box::new(NgramModel::english(3))];
+    for scorer in &scorers {
+        let (key, _, _) = most_likely_xor(&ciphertext, scorer.as_ref()).unwrap();
+        assert_eq!(key, b'X');
+    }
+}
+
+#[test]
+fn test_short_punctuated_text() {
+    use bytes::{most_likely_xor, xor1};
+
+    // Too short for the heuristic, which prefers "Idmmn-!Vnsme " - all letters
+    // and a space - to the real thing.
+    let ciphertext = xor1(b"Hello, World!", 0x5a);
+    assert_ne!(most_likely_xor(&ciphertext, &Heuristic).unwrap().0, 0x5a);
+    assert_eq!(most_likely_xor(&ciphertext, &ChiSquared::english()).unwrap().0, 0x5a);
+    assert_eq!(most_likely_xor(&ciphertext, &NgramModel::english(2)).unwrap().0, 0x5a);
+
+    // only trigrams get this one
+    let ciphertext = xor1(b"(see p. 12)", 0x5a);
+    assert_eq!(most_likely_xor(&ciphertext, &NgramModel::english(3)).unwrap().0, 0x5a);
+}
+
+#[test]
+fn test_training() {
+    use bytes::xor1;
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/google-10000-english-usa.txt");
+    let chi_squared = ChiSquared::from_file(path).unwrap();
+    let trigrams = NgramModel::from_file(path, 3).unwrap();
+    assert!(ChiSquared::from_file("no such file").is_err());
+
+    let english = b"the quick brown fox jumps over the lazy dog";
+    let shuffled = b"eht kciuq nworb xof spmuj revo eht yzal god";
+    let garbage = xor1(english, 0xa5);
+    // letter counts can't tell English from its reversal, but trigrams can
+    assert!(chi_squared.score(english) > chi_squared.score(&garbage));
+    assert!(trigrams.score(english) > trigrams.score(shuffled));
+    assert!(trigrams.score(shuffled) > trigrams.score(&garbage));
+
+    // a corpus of its own
+    let model = NgramModel::from_corpus(b"abababab", 2);
+    assert!(model.score(b"abab") > model.score(b"aabb"));
+}
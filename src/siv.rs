@@ -0,0 +1,130 @@
+use aes::{apply_ctr_with_layout, CtrLayout};
+use aescore::{Aes, BLOCK_SIZE};
+use blockcipher::CipherError;
+use bytes::inplace_xor;
+use mac::{cmac_with, double, verify_mac};
+
+// SIV (RFC 5297), deterministic authenticated encryption.  The IV is a MAC
+// (S2V, built from CMAC) of the associated data and the cleartext, and the
+// cleartext is then CTR encrypted with it.  The same inputs always give the same
+// ciphertext, which is the point for deterministic encryption and key wrapping;
+// if that's not wanted, a nonce can go in as the last piece of associated data.
+//
+// The key is the MAC key followed by the CTR key, so 32, 48 or 64 bytes.
+
+// returns V || C
+#[allow(dead_code)]
+pub fn siv_encrypt(key: &[u8],
+                   associated_data: &[&[u8]],
+                   cleartext: &[u8])
+                   -> Result<Vec<u8>, CipherError> {
+    let (mac_cipher, ctr_cipher) = try!(split_key(key));
+    let mut v = try!(s2v(&mac_cipher, associated_data, cleartext));
+    let ciphertext = try!(siv_ctr(&ctr_cipher, &v, cleartext));
+    v.extend(ciphertext);
+    Ok(v)
+}
+
+#[allow(dead_code)]
+pub fn siv_decrypt(key: &[u8],
+                   associated_data: &[&[u8]],
+                   ciphertext: &[u8])
+                   -> Result<Vec<u8>, CipherError> {
+    if ciphertext.len() < BLOCK_SIZE {
+        return Err(CipherError::InvalidLength);
+    }
+    let (mac_cipher, ctr_cipher) = try!(split_key(key));
+    let (v, ciphertext) = ciphertext.split_at(BLOCK_SIZE);
+    let cleartext = try!(siv_ctr(&ctr_cipher, v, ciphertext));
+    let expected = try!(s2v(&mac_cipher, associated_data, &cleartext));
+    if !verify_mac(&expected, v) {
+        return Err(CipherError::AuthenticationFailed);
+    }
+    Ok(cleartext)
+}
+
+#[allow(dead_code)]
+fn split_key(key: &[u8]) -> Result<(Aes, Aes), CipherError> {
+    if key.len() != 32 && key.len() != 48 && key.len() != 64 {
+        return Err(CipherError::InvalidKeyLength);
+    }
+    let (mac_key, ctr_key) = key.split_at(key.len() / 2);
+    Ok((try!(Aes::new(mac_key)), try!(Aes::new(ctr_key))))
+}
+
+// S2V, a CMAC over a vector of strings.  Each piece of associated data is
+// CMACed and folded in with a doubling; the cleartext goes into the final CMAC
+// directly.
+#[allow(dead_code)]
+pub fn s2v(cipher: &Aes,
+           associated_data: &[&[u8]],
+           cleartext: &[u8])
+           -> Result<Vec<u8>, CipherError> {
+    let mut d = try!(cmac_with(cipher, &[0u8; BLOCK_SIZE]));
+    for ad in associated_data {
+        d = inplace_xor(double(&d, 0x87), &try!(cmac_with(cipher, ad)));
+    }
+
+    let t = if cleartext.len() >= BLOCK_SIZE {
+        // xor d into the end of the cleartext
+        let mut t = cleartext.to_vec();
+        let start = t.len() - BLOCK_SIZE;
+        for (b, x) in t[start..].iter_mut().zip(d.iter()) {
+            *b ^= *x;
+        }
+        t
+    } else {
+        let mut padded = cleartext.to_vec();
+        padded.push(0x80);
+        padded.resize(BLOCK_SIZE, 0);
+        inplace_xor(double(&d, 0x87), &padded)
+    };
+    cmac_with(cipher, &t)
+}
+
+// CTR with a 128 bit big endian counter, starting at V with two bits cleared
+// (so that implementations can get away with a 64 or 32 bit counter)
+#[allow(dead_code)]
+fn siv_ctr(cipher: &Aes, v: &[u8], data: &[u8]) -> Result<Vec<u8>, CipherError> {
+    let mut q = v.to_vec();
+    q[8] &= 0x7f;
+    q[12] &= 0x7f;
+    apply_ctr_with_layout(cipher, &CtrLayout::full_block(), &q, data)
+}
+
+#[test]
+fn test_rfc5297() {
+    use hexstring::{fromhex, tohex};
+
+    // A.1, deterministic
+    let key = fromhex("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap();
+    let ad = fromhex("101112131415161718191a1b1c1d1e1f2021222324252627").unwrap();
+    let cleartext = fromhex("112233445566778899aabbccddee").unwrap();
+    let ciphertext = siv_encrypt(&key, &[&ad], &cleartext).unwrap();
+    assert_eq!(tohex(&ciphertext),
+               "85632d07c6e8f37f950acd320a2ecc9340c02b9690c4dc04daef7f6afe5c");
+    assert_eq!(siv_decrypt(&key, &[&ad], &ciphertext).unwrap(), cleartext);
+
+    // A.2, with a nonce as the last associated data
+    let key = fromhex("7f7e7d7c7b7a79787776757473727170404142434445464748494a4b4c4d4e4f").unwrap();
+    let ad1 = fromhex("00112233445566778899aabbccddeeffdeaddadadeaddadaffeeddccbbaa9988\
+                       7766554433221100")
+            .unwrap();
+    let ad2 = fromhex("102030405060708090a0").unwrap();
+    let nonce = fromhex("09f911029d74e35bd84156c5635688c0").unwrap();
+    let cleartext = b"this is some plaintext to encrypt using SIV-AES";
+    let ciphertext = siv_encrypt(&key, &[&ad1, &ad2, &nonce], cleartext).unwrap();
+    assert_eq!(tohex(&ciphertext),
+               "7bdb6e3b432667eb06f4d14bff2fbd0fcb900f2fddbe404326601965c889bf17\
+                dba77ceb094fa663b7a3f748ba8af829ea64ad544a272e9c485b62a3fd5c0d");
+    assert_eq!(siv_decrypt(&key, &[&ad1, &ad2, &nonce], &ciphertext).unwrap(),
+               cleartext.to_vec());
+
+    // dropping the nonce, or changing anything, fails
+    assert_eq!(siv_decrypt(&key, &[&ad1, &ad2], &ciphertext),
+               Err(CipherError::AuthenticationFailed));
+    let mut tampered = ciphertext.clone();
+    tampered[20] ^= 1;
+    assert_eq!(siv_decrypt(&key, &[&ad1, &ad2, &nonce], &tampered),
+               Err(CipherError::AuthenticationFailed));
+}
@@ -0,0 +1,381 @@
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use aes::fill_keystream_block;
+use blockcipher::{BlockCipher, CipherError, MAX_BLOCK_SIZE};
+
+// Streaming versions of the CBC and CTR modes in aes.rs.  Data can be fed in
+// chunks of any size and the output is byte-for-byte what the one-shot
+// functions produce for the whole message.  Nothing is allocated once a
+// stream is made: each keeps the block it's working on in a fixed size
+// buffer, of which only the first block_size() bytes are used.
+
+// how much a CtrStream transforms on the stack at a time when writing
+const WRITE_CHUNK_SIZE: usize = 256;
+
+// CBC encrypts to an inner writer a block at a time.  Like encrypt_cbc, the
+// cleartext must be a whole number of blocks (pad it first) - finish() fails if
+// there's a partial block left over.
+#[allow(dead_code)]
+pub struct CbcEncryptor<C: BlockCipher, W: Write> {
+    cipher: C,
+    inner: W,
+    last_block: [u8; MAX_BLOCK_SIZE],
+    pending: [u8; MAX_BLOCK_SIZE],
+    pending_len: usize,
+}
+
+impl<C: BlockCipher, W: Write> CbcEncryptor<C, W> {
+    #[allow(dead_code)]
+    pub fn new(cipher: C, iv: &[u8], inner: W) -> Result<CbcEncryptor<C, W>, CipherError> {
+        let last_block = try!(block_buffer(&cipher, iv));
+        Ok(CbcEncryptor {
+               cipher: cipher,
+               inner: inner,
+               last_block: last_block,
+               pending: [0u8; MAX_BLOCK_SIZE],
+               pending_len: 0,
+           })
+    }
+
+    #[allow(dead_code)]
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.pending_len != 0 {
+            return Err(partial_block_error());
+        }
+        try!(self.inner.flush());
+        Ok(self.inner)
+    }
+}
+
+impl<C: BlockCipher, W: Write> Write for CbcEncryptor<C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let block_size = self.cipher.block_size();
+        for b in buf {
+            let i = self.pending_len;
+            self.pending[i] = b ^ self.last_block[i];
+            self.pending_len += 1;
+            if self.pending_len == block_size {
+                self.cipher.encrypt_block(&mut self.pending[..block_size]);
+                try!(self.inner.write_all(&self.pending[..block_size]));
+                self.last_block = self.pending;
+                self.pending_len = 0;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// CBC decrypts to an inner writer.  Padding is left for the caller to remove,
+// same as decrypt_cbc.
+#[allow(dead_code)]
+pub struct CbcDecryptor<C: BlockCipher, W: Write> {
+    cipher: C,
+    inner: W,
+    last_block: [u8; MAX_BLOCK_SIZE],
+    pending: [u8; MAX_BLOCK_SIZE],
+    pending_len: usize,
+    // the block being decrypted, so that pending can still be fed back
+    block: [u8; MAX_BLOCK_SIZE],
+}
+
+impl<C: BlockCipher, W: Write> CbcDecryptor<C, W> {
+    #[allow(dead_code)]
+    pub fn new(cipher: C, iv: &[u8], inner: W) -> Result<CbcDecryptor<C, W>, CipherError> {
+        let last_block = try!(block_buffer(&cipher, iv));
+        Ok(CbcDecryptor {
+               cipher: cipher,
+               inner: inner,
+               last_block: last_block,
+               pending: [0u8; MAX_BLOCK_SIZE],
+               pending_len: 0,
+               block: [0u8; MAX_BLOCK_SIZE],
+           })
+    }
+
+    #[allow(dead_code)]
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.pending_len != 0 {
+            return Err(partial_block_error());
+        }
+        try!(self.inner.flush());
+        Ok(self.inner)
+    }
+}
+
+impl<C: BlockCipher, W: Write> Write for CbcDecryptor<C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let block_size = self.cipher.block_size();
+        for b in buf {
+            self.pending[self.pending_len] = *b;
+            self.pending_len += 1;
+            if self.pending_len == block_size {
+                self.block = self.pending;
+                self.cipher.decrypt_block(&mut self.block[..block_size]);
+                for (c, p) in self.block.iter_mut().zip(self.last_block.iter()) {
+                    *c ^= *p;
+                }
+                try!(self.inner.write_all(&self.block[..block_size]));
+                self.last_block = self.pending;
+                self.pending_len = 0;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// the IV in a block buffer, checking it's a block long
+#[allow(dead_code)]
+fn block_buffer<C: BlockCipher>(cipher: &C,
+                                iv: &[u8])
+                                -> Result<[u8; MAX_BLOCK_SIZE], CipherError> {
+    if iv.len() != cipher.block_size() || iv.len() > MAX_BLOCK_SIZE {
+        return Err(CipherError::InvalidLength);
+    }
+    let mut block = [0u8; MAX_BLOCK_SIZE];
+    block[..iv.len()].copy_from_slice(iv);
+    Ok(block)
+}
+
+#[allow(dead_code)]
+fn partial_block_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput,
+                   "data was not a multiple of the block size")
+}
+
+// CTR mode over any reader or writer.  Reading from a CtrStream decrypts (or
+// encrypts - it's the same thing) whatever the inner reader gives back; writing
+// to it passes the transformed bytes on to the inner writer.
+pub struct CtrStream<C: BlockCipher, S> {
+    cipher: C,
+    inner: S,
+    nonce: u64,
+    block_num: u64,
+    keystream: [u8; MAX_BLOCK_SIZE],
+    keystream_pos: usize,
+}
+
+impl<C: BlockCipher, S> CtrStream<C, S> {
+    pub fn new(cipher: C, nonce: u64, inner: S) -> Result<CtrStream<C, S>, CipherError> {
+        let block_size = cipher.block_size();
+        if block_size > MAX_BLOCK_SIZE {
+            return Err(CipherError::InvalidLength);
+        }
+        Ok(CtrStream {
+               cipher: cipher,
+               inner: inner,
+               nonce: nonce,
+               block_num: 0,
+               keystream: [0u8; MAX_BLOCK_SIZE],
+               // used up, so the first byte fetches a block
+               keystream_pos: block_size,
+           })
+    }
+
+    // xor the next data.len() bytes of keystream into data
+    pub fn apply_keystream(&mut self, data: &mut [u8]) {
+        let block_size = self.cipher.block_size();
+        for b in data.iter_mut() {
+            if self.keystream_pos == block_size {
+                self.next_keystream_block();
+                self.keystream_pos = 0;
+            }
+            *b ^= self.keystream[self.keystream_pos];
+            self.keystream_pos += 1;
+        }
+    }
+
+    // Move the keystream to `offset` bytes from the start of the message.  This
+    // doesn't move the inner stream - use Seek for that.
+    pub fn seek_keystream(&mut self, offset: u64) {
+        let block_size = self.cipher.block_size() as u64;
+        self.block_num = offset / block_size;
+        self.next_keystream_block();
+        self.keystream_pos = (offset % block_size) as usize;
+    }
+
+    fn next_keystream_block(&mut self) {
+        let block_size = self.cipher.block_size();
+        fill_keystream_block(&self.cipher,
+                             &self.nonce,
+                             self.block_num,
+                             &mut self.keystream[..block_size]);
+        self.block_num += 1;
+    }
+
+    #[allow(dead_code)]
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<C: BlockCipher, R: Read> Read for CtrStream<C, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = try!(self.inner.read(buf));
+        self.apply_keystream(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<C: BlockCipher, W: Write> Write for CtrStream<C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut transformed = [0u8; WRITE_CHUNK_SIZE];
+        for chunk in buf.chunks(WRITE_CHUNK_SIZE) {
+            let transformed = &mut transformed[..chunk.len()];
+            transformed.copy_from_slice(chunk);
+            self.apply_keystream(transformed);
+            try!(self.inner.write_all(transformed));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Seeking moves the inner stream and the keystream together, so an encrypted
+// file can be read or overwritten at any position.  The inner stream's
+// position 0 is assumed to be the start of the message.
+impl<C: BlockCipher, S: Seek> Seek for CtrStream<C, S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let offset = try!(self.inner.seek(pos));
+        self.seek_keystream(offset);
+        Ok(offset)
+    }
+}
+
+// write data to w in randomly sized chunks
+#[cfg(test)]
+fn write_in_chunks<W: Write>(w: &mut W, data: &[u8]) {
+    use bytes::rand_u8;
+
+    let mut remaining = data;
+    while !remaining.is_empty() {
+        let n = (rand_u8() as usize % 40).min(remaining.len());
+        w.write_all(&remaining[..n]).unwrap();
+        remaining = &remaining[n..];
+    }
+}
+
+#[test]
+fn test_cbc_stream() {
+    use aes::{encrypt_cbc, decrypt_cbc};
+    use aescore::Aes;
+    use bytes::random_bytes;
+
+    let key = random_bytes(16);
+    let iv = random_bytes(16);
+    for num_blocks in 0..20 {
+        let cleartext = random_bytes(num_blocks * 16);
+
+        let mut encryptor = CbcEncryptor::new(Aes::new(&key).unwrap(), &iv, Vec::new()).unwrap();
+        write_in_chunks(&mut encryptor, &cleartext);
+        let ciphertext = encryptor.finish().unwrap();
+        assert_eq!(ciphertext, encrypt_cbc(&cleartext, &key, &iv).unwrap());
+
+        let mut decryptor = CbcDecryptor::new(Aes::new(&key).unwrap(), &iv, Vec::new()).unwrap();
+        write_in_chunks(&mut decryptor, &ciphertext);
+        assert_eq!(decryptor.finish().unwrap(),
+                   decrypt_cbc(&ciphertext, &key, &iv).unwrap());
+    }
+
+    let mut encryptor = CbcEncryptor::new(Aes::new(&key).unwrap(), &iv, Vec::new()).unwrap();
+    encryptor.write_all(b"not a block").unwrap();
+    assert!(encryptor.finish().is_err());
+}
+
+#[test]
+fn test_ctr_stream() {
+    use aes::encrypt_ctr;
+    use aescore::Aes;
+    use bytes::random_bytes;
+    use std::io::Cursor;
+
+    let key = random_bytes(16);
+    for len in 0..100 {
+        let cleartext = random_bytes(len * 7);
+        let expected = encrypt_ctr(&cleartext, &key, &42).unwrap();
+
+        let mut writer = CtrStream::new(Aes::new(&key).unwrap(), 42, Vec::new()).unwrap();
+        write_in_chunks(&mut writer, &cleartext);
+        assert_eq!(writer.into_inner(), expected);
+
+        let mut reader = CtrStream::new(Aes::new(&key).unwrap(), 42, Cursor::new(expected))
+            .unwrap();
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, cleartext);
+    }
+}
+
+#[test]
+fn test_ctr_seek() {
+    use aes::encrypt_ctr;
+    use aescore::Aes;
+    use bytes::random_bytes;
+    use std::io::Cursor;
+
+    let key = random_bytes(16);
+    let cleartext = random_bytes(200);
+    let ciphertext = encrypt_ctr(&cleartext, &key, &1).unwrap();
+    let mut reader = CtrStream::new(Aes::new(&key).unwrap(), 1, Cursor::new(ciphertext.clone()))
+        .unwrap();
+    for offset in [150, 0, 17, 16, 199, 33].iter() {
+        reader.seek(SeekFrom::Start(*offset)).unwrap();
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], cleartext[*offset as usize]);
+    }
+
+    // overwrite the middle of an existing ciphertext
+    let mut writer = CtrStream::new(Aes::new(&key).unwrap(), 1, Cursor::new(ciphertext)).unwrap();
+    writer.seek(SeekFrom::Start(50)).unwrap();
+    writer.write_all(b"hello").unwrap();
+    let mut expected = cleartext.clone();
+    expected[50..55].copy_from_slice(b"hello");
+    let edited = writer.into_inner().into_inner();
+    assert_eq!(encrypt_ctr(&edited, &key, &1).unwrap(), expected);
+}
+
+#[test]
+fn test_small_blocks() {
+    use aes::{apply_ctr_in_place_with, encrypt_cbc_with};
+    use blockcipher::ToyCipher;
+    use bytes::random_bytes;
+
+    for block_size in 1..9 {
+        let cipher = ToyCipher::new(b"key", block_size).unwrap();
+        let iv = random_bytes(block_size);
+        let cleartext = random_bytes(block_size * 30);
+
+        let mut encryptor = CbcEncryptor::new(ToyCipher::new(b"key", block_size).unwrap(),
+                                              &iv,
+                                              Vec::new())
+                .unwrap();
+        write_in_chunks(&mut encryptor, &cleartext);
+        assert_eq!(encryptor.finish().unwrap(),
+                   encrypt_cbc_with(&cipher, &cleartext, &iv).unwrap());
+
+        let mut writer = CtrStream::new(ToyCipher::new(b"key", block_size).unwrap(), 9, Vec::new())
+            .unwrap();
+        write_in_chunks(&mut writer, &cleartext);
+        let mut expected = cleartext.clone();
+        apply_ctr_in_place_with(&cipher, &mut expected, &9);
+        assert_eq!(writer.into_inner(), expected);
+    }
+
+    // blocks bigger than the buffers are turned away
+    let cipher = ToyCipher::new(b"key", MAX_BLOCK_SIZE + 1).unwrap();
+    assert!(CbcEncryptor::new(cipher, &[0u8; MAX_BLOCK_SIZE + 1], Vec::new()).is_err());
+    let cipher = ToyCipher::new(b"key", MAX_BLOCK_SIZE + 1).unwrap();
+    assert_eq!(CtrStream::new(cipher, 9, ()).err(), Some(CipherError::InvalidLength));
+}
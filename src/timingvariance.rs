@@ -0,0 +1,90 @@
+use aescore::{Aes, Backend};
+use benchmark::bench_for;
+use bytes::random_bytes;
+use std::hint::black_box;
+use std::time::Duration;
+
+// Not one of the challenges - how much the time to encrypt a block depends on
+// the key, for each AES backend.  Each key gets a median time per block; the
+// spread of those medians across keys is what a timing attack has to work
+// with.  The table-based backend's lookups all hit a 256 byte table that
+// lives in L1 on a quiet machine, so expect the difference to be small here -
+// it shows up with a noisy neighbour evicting cache lines.  Run with --release.
+
+static NUM_KEYS: usize = 16;
+
+fn budget() -> Duration {
+    Duration::from_millis(20)
+}
+
+#[derive(Debug, PartialEq)]
+struct TimingStats {
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+}
+
+fn timing_stats(samples: &[f64]) -> TimingStats {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = samples.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / n;
+    TimingStats {
+        mean: mean,
+        std_dev: variance.sqrt(),
+        min: samples.iter().cloned().fold(f64::INFINITY, f64::min),
+        max: samples.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+
+// the median time per block for each of the keys
+fn time_keys<F: Fn(&[u8]) -> Aes>(make_cipher: F, keys: &[Vec<u8>]) -> Vec<f64> {
+    let cleartext = random_bytes(16);
+    keys.iter()
+        .map(|key| {
+                 let aes = make_cipher(key);
+                 let mut block = cleartext.clone();
+                 bench_for(budget(), || aes.encrypt_block(black_box(&mut block)))
+             })
+        .collect()
+}
+
+pub fn timingvariance() {
+    let keys: Vec<Vec<u8>> = (0..NUM_KEYS).map(|_| random_bytes(16)).collect();
+    let mut backends: Vec<(Backend, fn(&[u8]) -> Aes)> =
+        vec![(Backend::Portable, |key| Aes::new_portable(key).unwrap()),
+             (Backend::Bitsliced, |key| Aes::new_bitsliced(key).unwrap())];
+    if Aes::new(&keys[0]).unwrap().backend() == Backend::AesNi {
+        backends.push((Backend::AesNi, |key| Aes::new(key).unwrap()));
+    }
+
+    println!("{:<10} {:>10} {:>10} {:>10} {:>10} {:>8}",
+             "ns/block",
+             "mean",
+             "std dev",
+             "min",
+             "max",
+             "cv");
+    for (backend, make_cipher) in backends {
+        let stats = timing_stats(&time_keys(make_cipher, &keys));
+        println!("{:<10} {:>10.1} {:>10.2} {:>10.1} {:>10.1} {:>7.2}%",
+                 format!("{:?}", backend),
+                 stats.mean,
+                 stats.std_dev,
+                 stats.min,
+                 stats.max,
+                 100.0 * stats.std_dev / stats.mean);
+    }
+}
+
+#[test]
+fn test_timing_stats() {
+    let stats = timing_stats(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+    assert_eq!(stats,
+               TimingStats {
+                   mean: 5.0,
+                   std_dev: 2.0,
+                   min: 2.0,
+                   max: 9.0,
+               });
+}
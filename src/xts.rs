@@ -0,0 +1,225 @@
+use aescore::Aes;
+use blockcipher::{BlockCipher, CipherError};
+
+// XTS (IEEE 1619), the disk encryption mode.  Each sector is encrypted
+// separately, with a tweak made by encrypting the sector number under a second
+// key.  Every block is xored with the tweak before and after encryption, and
+// the tweak is multiplied by x in GF(2^128) for each block, so the same
+// cleartext encrypts differently at every position on the disk.  A partial
+// last block uses ciphertext stealing, so a sector only has to be at least a
+// block long.
+
+// The key is the data key followed by the tweak key, so 32 or 64 bytes.
+#[allow(dead_code)]
+pub fn encrypt_xts(cleartext: &[u8], key: &[u8], sector: u64) -> Result<Vec<u8>, CipherError> {
+    let (data_cipher, tweak_cipher) = try!(split_key(key));
+    encrypt_xts_with(&data_cipher, &tweak_cipher, &sector_tweak(sector), cleartext)
+}
+
+#[allow(dead_code)]
+pub fn decrypt_xts(ciphertext: &[u8], key: &[u8], sector: u64) -> Result<Vec<u8>, CipherError> {
+    let (data_cipher, tweak_cipher) = try!(split_key(key));
+    decrypt_xts_with(&data_cipher, &tweak_cipher, &sector_tweak(sector), ciphertext)
+}
+
+#[allow(dead_code)]
+fn split_key(key: &[u8]) -> Result<(Aes, Aes), CipherError> {
+    if key.len() != 32 && key.len() != 64 {
+        return Err(CipherError::InvalidKeyLength);
+    }
+    let (data_key, tweak_key) = key.split_at(key.len() / 2);
+    Ok((try!(Aes::new(data_key)), try!(Aes::new(tweak_key))))
+}
+
+// the sector number as a little endian block
+#[allow(dead_code)]
+pub fn sector_tweak(sector: u64) -> [u8; 16] {
+    let mut tweak = [0u8; 16];
+    for (i, t) in tweak.iter_mut().take(8).enumerate() {
+        *t = (sector >> (8 * i)) as u8;
+    }
+    tweak
+}
+
+// multiply by x - unlike GCM, XTS treats the block as a little endian number
+#[allow(dead_code)]
+fn next_tweak(tweak: &mut [u8]) {
+    let mut carry = 0u8;
+    for b in tweak.iter_mut() {
+        let next_carry = *b >> 7;
+        *b = (*b << 1) | carry;
+        carry = next_carry;
+    }
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
+
+#[allow(dead_code)]
+fn xor_tweak(block: &mut [u8], tweak: &[u8]) {
+    for (b, t) in block.iter_mut().zip(tweak) {
+        *b ^= *t;
+    }
+}
+
+#[allow(dead_code)]
+fn encrypt_block<C: BlockCipher>(cipher: &C, block: &mut [u8], tweak: &[u8]) {
+    xor_tweak(block, tweak);
+    cipher.encrypt_block(block);
+    xor_tweak(block, tweak);
+}
+
+#[allow(dead_code)]
+fn decrypt_block<C: BlockCipher>(cipher: &C, block: &mut [u8], tweak: &[u8]) {
+    xor_tweak(block, tweak);
+    cipher.decrypt_block(block);
+    xor_tweak(block, tweak);
+}
+
+// the tweak for each block (including a partial last one)
+#[allow(dead_code)]
+fn tweaks<C: BlockCipher>(tweak_cipher: &C, tweak: &[u8], num_blocks: usize) -> Vec<Vec<u8>> {
+    let mut t = tweak.to_vec();
+    tweak_cipher.encrypt_block(&mut t);
+    let mut tweaks = Vec::with_capacity(num_blocks);
+    for _ in 0..num_blocks {
+        tweaks.push(t.clone());
+        next_tweak(&mut t);
+    }
+    tweaks
+}
+
+#[allow(dead_code)]
+fn check_lengths<C: BlockCipher>(data_cipher: &C,
+                                 tweak_cipher: &C,
+                                 tweak: &[u8],
+                                 data: &[u8])
+                                 -> Result<(), CipherError> {
+    let block_size = data_cipher.block_size();
+    if block_size != 16 || tweak_cipher.block_size() != 16 || tweak.len() != 16 ||
+       data.len() < block_size {
+        Err(CipherError::InvalidLength)
+    } else {
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+pub fn encrypt_xts_with<C: BlockCipher>(data_cipher: &C,
+                                        tweak_cipher: &C,
+                                        tweak: &[u8],
+                                        cleartext: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    try!(check_lengths(data_cipher, tweak_cipher, tweak, cleartext));
+    let num_blocks = (cleartext.len() + 15) / 16;
+    let tweaks = tweaks(tweak_cipher, tweak, num_blocks);
+    let mut ciphertext = cleartext.to_vec();
+    let full_len = cleartext.len() / 16 * 16;
+    for (block, t) in ciphertext[..full_len].chunks_mut(16).zip(tweaks.iter()) {
+        encrypt_block(data_cipher, block, t);
+    }
+
+    // Ciphertext stealing - the last full block's ciphertext is cut down to
+    // become the partial last block, and the bytes cut off fill out the
+    // partial cleartext block, which is encrypted in the last full block's
+    // place.
+    let partial_len = cleartext.len() - full_len;
+    if partial_len > 0 {
+        let last_full = full_len - 16;
+        let mut stolen = cleartext[full_len..].to_vec();
+        stolen.extend_from_slice(&ciphertext[last_full + partial_len..full_len]);
+        encrypt_block(data_cipher, &mut stolen, &tweaks[num_blocks - 1]);
+        let (last_full_block, partial_block) = ciphertext.split_at_mut(full_len);
+        partial_block.copy_from_slice(&last_full_block[last_full..last_full + partial_len]);
+        last_full_block[last_full..].copy_from_slice(&stolen);
+    }
+    Ok(ciphertext)
+}
+
+#[allow(dead_code)]
+pub fn decrypt_xts_with<C: BlockCipher>(data_cipher: &C,
+                                        tweak_cipher: &C,
+                                        tweak: &[u8],
+                                        ciphertext: &[u8])
+                                        -> Result<Vec<u8>, CipherError> {
+    try!(check_lengths(data_cipher, tweak_cipher, tweak, ciphertext));
+    let num_blocks = (ciphertext.len() + 15) / 16;
+    let tweaks = tweaks(tweak_cipher, tweak, num_blocks);
+    let mut cleartext = ciphertext.to_vec();
+    let full_len = ciphertext.len() / 16 * 16;
+    let partial_len = ciphertext.len() - full_len;
+
+    // with stealing, the last full block was encrypted with the final tweak
+    // and has to be done last
+    let normal_len = if partial_len > 0 { full_len - 16 } else { full_len };
+    for (block, t) in cleartext[..normal_len].chunks_mut(16).zip(tweaks.iter()) {
+        decrypt_block(data_cipher, block, t);
+    }
+
+    if partial_len > 0 {
+        let mut stolen = ciphertext[normal_len..full_len].to_vec();
+        decrypt_block(data_cipher, &mut stolen, &tweaks[num_blocks - 1]);
+        let mut last_full_block = ciphertext[full_len..].to_vec();
+        last_full_block.extend_from_slice(&stolen[partial_len..]);
+        decrypt_block(data_cipher, &mut last_full_block, &tweaks[num_blocks - 2]);
+        cleartext[normal_len..full_len].copy_from_slice(&last_full_block);
+        cleartext[full_len..].copy_from_slice(&stolen[..partial_len]);
+    }
+    Ok(cleartext)
+}
+
+#[test]
+fn test_ieee1619() {
+    use hexstring::{fromhex, tohex};
+
+    // vectors 1 and 2
+    let cleartext = [0u8; 32];
+    let ciphertext = encrypt_xts(&cleartext, &[0u8; 32], 0).unwrap();
+    assert_eq!(tohex(&ciphertext),
+               "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e");
+    assert_eq!(decrypt_xts(&ciphertext, &[0u8; 32], 0).unwrap(), cleartext);
+
+    let mut key = vec![0x11u8; 16];
+    key.extend_from_slice(&[0x22u8; 16]);
+    let cleartext = [0x44u8; 32];
+    let ciphertext = encrypt_xts(&cleartext, &key, 0x3333333333).unwrap();
+    assert_eq!(tohex(&ciphertext),
+               "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0");
+    assert_eq!(decrypt_xts(&ciphertext, &key, 0x3333333333).unwrap(), cleartext);
+
+    // vectors 15 to 18, with partial last blocks
+    let key = fromhex("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0").unwrap();
+    let cleartext: Vec<u8> = (0..20).collect();
+    let expected = ["6c1625db4671522d3d7599601de7ca09ed",
+                    "d069444b7a7e0cab09e24447d24deb1fedbf",
+                    "e5df1351c0544ba1350b3363cd8ef4beedbf9d",
+                    "9d84c813f719aa2c7be3f66171c7c5c2edbf9dac"];
+    for (len, ciphertext) in (17..21).zip(expected.iter()) {
+        let encrypted = encrypt_xts(&cleartext[..len], &key, 0x123456789a).unwrap();
+        assert_eq!(&tohex(&encrypted), ciphertext);
+        assert_eq!(decrypt_xts(&encrypted, &key, 0x123456789a).unwrap(),
+                   &cleartext[..len]);
+    }
+}
+
+#[test]
+fn test_xts() {
+    use bytes::random_bytes;
+
+    let key = random_bytes(64);
+    for len in 16..100 {
+        let cleartext = random_bytes(len);
+        let ciphertext = encrypt_xts(&cleartext, &key, len as u64).unwrap();
+        assert_eq!(ciphertext.len(), len);
+        assert_eq!(decrypt_xts(&ciphertext, &key, len as u64).unwrap(), cleartext);
+    }
+
+    // the same block encrypts differently in different places
+    let cleartext = [7u8; 32];
+    let ciphertext = encrypt_xts(&cleartext, &key, 1).unwrap();
+    assert!(ciphertext[..16] != ciphertext[16..]);
+    assert!(ciphertext != encrypt_xts(&cleartext, &key, 2).unwrap());
+
+    assert_eq!(encrypt_xts(&[0u8; 15], &key, 0), Err(CipherError::InvalidLength));
+    assert_eq!(encrypt_xts(&[0u8; 16], &key[..48], 0), Err(CipherError::InvalidKeyLength));
+}